@@ -0,0 +1,42 @@
+//! `process_toml_string` 的基准测试
+//!
+//! 在小、中、大三个合成清单上计时，并测量已知平台依赖列表的查询（这个集合只在第一次使用时构建，
+//! 之后的查询没有分配）：
+//!
+//! ```bash
+//! cargo bench
+//! ```
+//!
+//! 性能回退检查（约 1 万行的清单不能超过时间上限）在 tests/perf.rs 中，是 `cargo test` 的一部分
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+mod synthetic;
+
+use synthetic::synthetic_manifest;
+
+fn process(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_toml_string");
+    for (name, targets) in [("small", 4), ("medium", 50), ("large", 500)] {
+        let content = synthetic_manifest(targets);
+        group.bench_with_input(BenchmarkId::from_parameter(name), &content, |b, content| {
+            b.iter(|| {
+                pre::process_toml_string(std::hint::black_box(content))
+                    .expect("合成清单应能正常处理")
+            })
+        });
+    }
+    group.finish();
+}
+
+fn lookups(c: &mut Criterion) {
+    c.bench_function("is_known_platform_dep", |b| {
+        b.iter(|| {
+            pre::is_known_platform_dep(std::hint::black_box("windows-sys"))
+                && !pre::is_known_platform_dep(std::hint::black_box("serde"))
+        })
+    });
+}
+
+criterion_group!(benches, process, lookups);
+criterion_main!(benches);
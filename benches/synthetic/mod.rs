@@ -0,0 +1,59 @@
+//! 合成清单生成器，benches/process.rs 和 tests/perf.rs 共用，各自只用到其中一部分
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+/// 性能回退检查（tests/perf.rs）使用的清单的 target 配置数，每个配置 8 个依赖，约 1 万行
+pub const CHECK_TARGETS: usize = 830;
+
+/// 性能回退检查的时间上限，留有足够余量，debug 构建也不会误报
+pub const CHECK_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// target 配置的条件，按顺序轮换：一半保留，一半删除
+const TARGET_SPECS: &[&str] = &[
+    "windows",
+    "unix",
+    "target_os = \"macos\"",
+    "target_os = \"linux\"",
+    "all(target_arch = \"wasm32\", target_os = \"unknown\")",
+    "any(target_os = \"linux\", target_os = \"android\")",
+];
+
+const DEPS_PER_TARGET: usize = 8;
+
+/// 生成合成清单：`targets` 个 target 配置，每个带若干可选依赖，features 引用其中的一部分
+pub fn synthetic_manifest(targets: usize) -> String {
+    let mut out = String::from(
+        "[package]\nname = \"synthetic\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n",
+    );
+    for i in 0..targets.max(1) {
+        out.push_str(&format!("common-{} = \"1.0\"\n", i));
+    }
+
+    let mut features = Vec::new();
+    for t in 0..targets {
+        // 加上不影响结果的 target_vendor 条件，使每个 target 配置的键各不相同
+        let spec = TARGET_SPECS[t % TARGET_SPECS.len()];
+        out.push_str(&format!(
+            "\n[target.'cfg(all({}, not(target_vendor = \"synthetic{}\")))'.dependencies]\n",
+            spec, t
+        ));
+        for d in 0..DEPS_PER_TARGET {
+            out.push_str(&format!(
+                "dep-{}-{} = {{ version = \"0.{}\", optional = true }}\n",
+                t, d, d
+            ));
+        }
+        features.push(format!(
+            "feature-{} = [\"dep:dep-{}-0\", \"dep-{}-1/std\", \"common-{}/std\"]",
+            t, t, t, t
+        ));
+    }
+
+    out.push_str("\n[features]\ndefault = []\n");
+    for feature in features {
+        out.push_str(&feature);
+        out.push('\n');
+    }
+    out
+}
@@ -1,4 +1,5 @@
 use cargo_toml::Manifest;
+use pre::{analyze_manifest, TargetFilter};
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
@@ -10,68 +11,70 @@ fn main() {
 
     let input_path = &args[1];
 
-    // 先使用 cargo_toml 分析依赖
-    match Manifest::from_path(input_path) {
-        Ok(manifest) => {
-            println!("=== 使用 cargo_toml 解析的依赖信息 ===\n");
-
-            // 显示常规依赖
-            if !manifest.dependencies.is_empty() {
-                println!("常规依赖 ({} 个):", manifest.dependencies.len());
-                for (name, _) in &manifest.dependencies {
-                    println!("  - {}", name);
-                }
-                println!();
-            }
-
-            // 显示 target-specific 依赖
-            if !manifest.target.is_empty() {
-                println!("平台特定依赖 ({} 个target配置):", manifest.target.len());
-                for (target, deps) in &manifest.target {
-                    let total_deps = deps.dependencies.len()
-                        + deps.dev_dependencies.len()
-                        + deps.build_dependencies.len();
-
-                    if total_deps > 0 {
-                        println!("\n  Target: {}", target);
+    let manifest = match Manifest::from_path(input_path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("✗ 无法解析 Cargo.toml: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // 只列出平台特定部分，不修改文件
+    let analysis = analyze_manifest(&manifest, &TargetFilter::default());
 
-                        if !deps.dependencies.is_empty() {
-                            println!("    dependencies:");
-                            for (name, _) in &deps.dependencies {
-                                println!("      - {}", name);
-                            }
-                        }
+    println!("=== 使用 cargo_toml 解析的依赖信息 ===\n");
 
-                        if !deps.dev_dependencies.is_empty() {
-                            println!("    dev-dependencies:");
-                            for (name, _) in &deps.dev_dependencies {
-                                println!("      - {}", name);
-                            }
-                        }
+    // 显示常规依赖
+    if !analysis.dependencies.is_empty() {
+        println!("常规依赖 ({} 个):", analysis.dependencies.len());
+        for name in &analysis.dependencies {
+            println!("  - {}", name);
+        }
+        println!();
+    }
 
-                        if !deps.build_dependencies.is_empty() {
-                            println!("    build-dependencies:");
-                            for (name, _) in &deps.build_dependencies {
-                                println!("      - {}", name);
-                            }
-                        }
+    // 显示 target-specific 依赖
+    if !analysis.targets.is_empty() {
+        println!("平台特定依赖 ({} 个target配置):", analysis.targets.len());
+        for target in &analysis.targets {
+            let mark = if target.removed {
+                "（会被删除）"
+            } else {
+                ""
+            };
+            println!("\n  Target: {}{}", target.spec, mark);
+            for (kind, deps) in [
+                ("dependencies", &target.dependencies),
+                ("dev-dependencies", &target.dev_dependencies),
+                ("build-dependencies", &target.build_dependencies),
+            ] {
+                if !deps.is_empty() {
+                    println!("    {}:", kind);
+                    for name in deps {
+                        println!("      - {}", name);
                     }
                 }
-                println!();
             }
+        }
+        println!();
+    }
 
-            // 显示 features
-            if !manifest.features.is_empty() {
-                println!("Features ({} 个):", manifest.features.len());
-                for (name, items) in &manifest.features {
-                    println!("  {} = {:?}", name, items);
-                }
-                println!();
-            }
+    if !analysis.platform_deps.is_empty() {
+        println!("会被删除的依赖 ({} 个):", analysis.platform_deps.len());
+        for (name, category) in &analysis.platform_deps {
+            println!("  - {} ({})", name, category);
         }
-        Err(e) => {
-            eprintln!("✗ 无法解析 Cargo.toml: {}", e);
-            std::process::exit(1);
+        println!();
+    }
+
+    // 显示 features
+    if !manifest.features.is_empty() {
+        println!("Features ({} 个):", manifest.features.len());
+        for (name, items) in &manifest.features {
+            match analysis.affected_features.get(name) {
+                Some(affected) => println!("  {} = {:?}，会删除 {:?}", name, items, affected),
+                None => println!("  {} = {:?}", name, items),
+            }
         }
+        println!();
     }
 }
@@ -0,0 +1,32 @@
+#!/bin/bash
+
+# 检查处理结果是幂等的：对 test 目录下的每个 .toml 文件，处理一次的输出再处理一次，结果必须完全相同
+# 用例开头注释中 "由 `pre <选项>` 生成" 的选项同样用于两次处理
+# cargo test 中的 tests/idempotence.rs 做同样的检查，这个脚本在结果变化时额外输出差异
+
+cargo build -q || exit 1
+
+tmp_dir=$(mktemp -d)
+trap 'rm -rf "$tmp_dir"' EXIT
+
+failed=0
+for input_file in test/*.toml; do
+    filename=$(basename "$input_file")
+    options=$(sed -n 's/.*由 `pre \(.*\)` 生成.*/\1/p' "$input_file" | head -1)
+
+    # 第一次处理出错的用例（例如预期报错的用例）跳过
+    ./target/debug/pre $options "$input_file" "$tmp_dir/once-$filename" >/dev/null 2>&1 || continue
+    if ! ./target/debug/pre $options "$tmp_dir/once-$filename" "$tmp_dir/twice-$filename" >/dev/null 2>&1; then
+        echo "✗ 错误: $input_file 的输出再次处理时出错"
+        failed=1
+    elif ! cmp -s "$tmp_dir/once-$filename" "$tmp_dir/twice-$filename"; then
+        echo "✗ 错误: $input_file 再次处理后结果发生变化"
+        diff "$tmp_dir/once-$filename" "$tmp_dir/twice-$filename"
+        failed=1
+    fi
+done
+
+if [ $failed -eq 0 ]; then
+    echo "✓ 所有用例的处理结果都是幂等的"
+fi
+exit $failed
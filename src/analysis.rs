@@ -0,0 +1,132 @@
+use crate::{
+    dep_package_names, get_known_platform_deps, known_dep_category, normalized_names,
+    should_remove_feature_item, should_remove_target_config, target_spec_category, TargetFilter,
+};
+use cargo_toml::Manifest;
+use std::collections::{BTreeMap, HashSet};
+
+/// 清单中的一个 target 配置，见 [`Analysis::targets`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TargetAnalysis {
+    /// target 配置键，例如 `cfg(windows)`
+    pub spec: String,
+    /// 处理时是否会被删除
+    pub removed: bool,
+    pub dependencies: Vec<String>,
+    pub dev_dependencies: Vec<String>,
+    pub build_dependencies: Vec<String>,
+}
+
+/// [`analyze_manifest`] 的结果：清单中的平台特定部分，不修改清单
+///
+/// 启用 `serde` feature 后可以序列化
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Analysis {
+    /// 顶层 `[dependencies]` 中的依赖
+    pub dependencies: Vec<String>,
+    /// 所有 target 配置，按清单中的键排序
+    pub targets: Vec<TargetAnalysis>,
+    /// 平台特定依赖 -> 分类（与 [`crate::ProcessReport::removed_by_category`] 的分类一致）
+    pub platform_deps: BTreeMap<String, String>,
+    /// 引用了平台特定依赖的 feature -> 其中引用这些依赖的条目
+    pub affected_features: BTreeMap<String, Vec<String>>,
+}
+
+impl Analysis {
+    /// 会被删除的 target 配置键
+    pub fn removed_targets(&self) -> impl Iterator<Item = &str> {
+        self.targets
+            .iter()
+            .filter(|target| target.removed)
+            .map(|target| target.spec.as_str())
+    }
+}
+
+/// 列出清单中按 `filter` 处理时的平台特定部分，不做任何删除
+///
+/// 平台特定依赖指只声明在会被删除的 target 配置中的依赖，以及（保留 Linux 时）顶层依赖表中
+/// 在内置已知平台依赖列表里的依赖；两者都不包括保留的 target 配置中仍然声明的依赖。
+/// 结果按默认选项计算，不考虑 [`crate::Processor`] 的 `keep_dep`、`known_deps` 等设置
+pub fn analyze_manifest(manifest: &Manifest, filter: &TargetFilter) -> Analysis {
+    let names = |deps: &cargo_toml::DepsSet| deps.keys().cloned().collect::<Vec<_>>();
+    let targets: Vec<TargetAnalysis> = manifest
+        .target
+        .iter()
+        .map(|(spec, target)| TargetAnalysis {
+            spec: spec.clone(),
+            removed: should_remove_target_config(spec, filter),
+            dependencies: names(&target.dependencies),
+            dev_dependencies: names(&target.dev_dependencies),
+            build_dependencies: names(&target.build_dependencies),
+        })
+        .collect();
+
+    let target_deps = |target: &TargetAnalysis| {
+        target
+            .dependencies
+            .iter()
+            .chain(&target.dev_dependencies)
+            .chain(&target.build_dependencies)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let kept: HashSet<String> = targets
+        .iter()
+        .filter(|target| !target.removed)
+        .flat_map(target_deps)
+        .collect();
+
+    let packages = dep_package_names(manifest);
+    let package_of = |dep: &str| packages.get(dep).map_or(dep, String::as_str).to_string();
+    let mut platform_deps = BTreeMap::new();
+    for target in targets.iter().filter(|target| target.removed) {
+        for dep in target_deps(target) {
+            if kept.contains(&dep) || manifest.dependencies.contains_key(&dep) {
+                continue;
+            }
+            let category = known_dep_category(&package_of(&dep))
+                .unwrap_or_else(|| target_spec_category(&target.spec));
+            platform_deps
+                .entry(dep)
+                .or_insert_with(|| category.to_string());
+        }
+    }
+    if filter.keeps_linux() {
+        let top_level = manifest
+            .dependencies
+            .keys()
+            .chain(manifest.dev_dependencies.keys())
+            .chain(manifest.build_dependencies.keys());
+        for dep in top_level {
+            let package = package_of(dep);
+            if !kept.contains(dep) && get_known_platform_deps().contains(package.as_str()) {
+                let category = known_dep_category(&package).unwrap_or("other");
+                platform_deps.insert(dep.clone(), category.to_string());
+            }
+        }
+    }
+
+    let removed: HashSet<String> = normalized_names(&platform_deps.keys().cloned().collect());
+    let declared_features: HashSet<String> = manifest.features.keys().cloned().collect();
+    let affected_features = manifest
+        .features
+        .iter()
+        .filter_map(|(feature, items)| {
+            let items: Vec<String> = items
+                .iter()
+                .filter(|item| should_remove_feature_item(item, &removed, &declared_features))
+                .cloned()
+                .collect();
+            (!items.is_empty()).then(|| (feature.clone(), items))
+        })
+        .collect();
+
+    Analysis {
+        dependencies: names(&manifest.dependencies),
+        targets,
+        platform_deps,
+        affected_features,
+    }
+}
@@ -0,0 +1,109 @@
+use crate::ProcessError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, DocumentMut, Item, Table};
+
+/// 增量处理缓存，记录每个文件上次处理后的内容哈希
+///
+/// 哈希同时包含配置指纹（见 [`crate::Processor::fingerprint`]）、工具版本和输出路径，
+/// 配置或版本变化后所有记录自动失效，同一个输入换一个输出文件时也会重新处理。缓存文件是 TOML 格式：
+///
+/// ```toml
+/// [files]
+/// "crates/foo/Cargo.toml" = "8c3b5f0d9a1e2f47"
+/// ```
+#[derive(Debug)]
+pub struct Cache {
+    path: PathBuf,
+    fingerprint: String,
+    entries: BTreeMap<String, String>,
+}
+
+impl Cache {
+    /// 读取缓存文件，不存在时返回空缓存
+    pub fn load(path: impl Into<PathBuf>, fingerprint: &str) -> Result<Cache, ProcessError> {
+        let path = path.into();
+        let mut entries = BTreeMap::new();
+        if path.exists() {
+            let doc = fs::read_to_string(&path)?.parse::<DocumentMut>()?;
+            if let Some(files) = doc.get("files").and_then(Item::as_table_like) {
+                for (file, hash) in files.iter() {
+                    if let Some(hash) = hash.as_str() {
+                        entries.insert(file.to_string(), hash.to_string());
+                    }
+                }
+            }
+        }
+        Ok(Cache {
+            path,
+            fingerprint: format!("{}|v{}", fingerprint, env!("CARGO_PKG_VERSION")),
+            entries,
+        })
+    }
+
+    /// 原地处理的文件内容与上次处理后的结果一致（配置也未变化），可以跳过
+    pub fn is_fresh(&self, file: &Path, content: &str) -> bool {
+        self.is_fresh_for(file, file, content)
+    }
+
+    /// 与 [`Cache::is_fresh`] 相同，上次处理的输出也必须是 `output`
+    pub fn is_fresh_for(&self, file: &Path, output: &Path, content: &str) -> bool {
+        self.entries
+            .get(file.to_string_lossy().as_ref())
+            .is_some_and(|hash| *hash == self.hash(output, content))
+    }
+
+    /// 记录原地处理的文件处理后的内容
+    pub fn record(&mut self, file: &Path, content: &str) {
+        self.record_for(file, file, content);
+    }
+
+    /// 记录文件处理后的内容，输出写到了 `output`
+    pub fn record_for(&mut self, file: &Path, output: &Path, content: &str) {
+        let hash = self.hash(output, content);
+        self.entries
+            .insert(file.to_string_lossy().into_owned(), hash);
+    }
+
+    /// 写回缓存文件
+    pub fn save(&self) -> Result<(), ProcessError> {
+        let mut files = Table::new();
+        for (file, hash) in &self.entries {
+            files.insert(file, value(hash.as_str()));
+        }
+        let mut doc = DocumentMut::new();
+        doc.insert("files", Item::Table(files));
+        fs::write(&self.path, doc.to_string())?;
+        Ok(())
+    }
+
+    fn hash(&self, output: &Path, content: &str) -> String {
+        let mut hash = Fnv1a::new();
+        hash.write(self.fingerprint.as_bytes());
+        hash.write(&[0]);
+        // 原地处理时与输入路径相同
+        hash.write(output.to_string_lossy().as_bytes());
+        hash.write(&[0]);
+        hash.write(content.as_bytes());
+        format!("{:016x}", hash.0)
+    }
+}
+
+/// FNV-1a 64 位哈希
+///
+/// 缓存文件需要跨进程、跨 Rust 版本保持稳定，不能使用 `DefaultHasher`
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
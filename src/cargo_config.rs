@@ -0,0 +1,145 @@
+use crate::{should_remove_target_config, write_output, ProcessError, TargetFilter};
+use std::fs;
+use toml_edit::{DocumentMut, Item, Value};
+
+/// `.cargo/config.toml` 的处理结果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CargoConfigReport {
+    /// 输出是否与输入不同
+    pub changed: bool,
+    /// 被删除的 `[target.xxx]` 表
+    pub removed_targets: Vec<String>,
+    /// 从 `[build] target` 中移除的不保留的 triple
+    pub removed_build_targets: Vec<String>,
+    /// 提示信息（不影响处理结果）
+    pub warnings: Vec<String>,
+}
+
+/// 处理 `.cargo/config.toml` 文件，删除非Linux平台相关的配置
+///
+/// `prune_build_targets` 为 true 时，会从 `[build] target` 数组中移除非 Linux triple
+pub fn process_cargo_config(
+    input_path: &str,
+    output_path: &str,
+    prune_build_targets: bool,
+) -> Result<CargoConfigReport, ProcessError> {
+    process_cargo_config_with_filter(
+        input_path,
+        output_path,
+        prune_build_targets,
+        &TargetFilter::default(),
+    )
+}
+
+/// 处理 `.cargo/config.toml` 文件，按指定的平台过滤条件删除 `[target.<triple>]` 和
+/// `[target.'cfg(...)']` 表
+///
+/// 配置文件中没有依赖和 features，只处理 target 表（runner、linker、rustflags 等）和 `[build] target`
+pub fn process_cargo_config_with_filter(
+    input_path: &str,
+    output_path: &str,
+    prune_build_targets: bool,
+    filter: &TargetFilter,
+) -> Result<CargoConfigReport, ProcessError> {
+    let content = fs::read_to_string(input_path)?;
+    let (output, report) =
+        process_cargo_config_string_with_filter(&content, prune_build_targets, filter)?;
+    write_output(output_path, &output)?;
+    Ok(report)
+}
+
+/// 处理 `.cargo/config.toml` 字符串
+pub fn process_cargo_config_string(
+    content: &str,
+    prune_build_targets: bool,
+) -> Result<(String, CargoConfigReport), ProcessError> {
+    process_cargo_config_string_with_filter(content, prune_build_targets, &TargetFilter::default())
+}
+
+/// 按指定的平台过滤条件处理 `.cargo/config.toml` 字符串，见 [`process_cargo_config_with_filter`]
+pub fn process_cargo_config_string_with_filter(
+    content: &str,
+    prune_build_targets: bool,
+    filter: &TargetFilter,
+) -> Result<(String, CargoConfigReport), ProcessError> {
+    let mut doc = content.parse::<DocumentMut>()?;
+    let mut report = CargoConfigReport::default();
+
+    // 1. 删除不保留的 target 表（runner、linker、rustflags 等）
+    if let Some(target_table) = doc.get_mut("target").and_then(|t| t.as_table_like_mut()) {
+        let keys_to_remove: Vec<String> = target_table
+            .iter()
+            .filter(|(key, _)| should_remove_target_config(key, filter))
+            .map(|(key, _)| key.to_string())
+            .collect();
+
+        for key in &keys_to_remove {
+            target_table.remove(key);
+        }
+
+        if target_table.is_empty() {
+            doc.remove("target");
+        }
+        report.removed_targets = keys_to_remove;
+    }
+
+    // 2. 检查 [build] target 默认目标列表
+    if let Some(build_target) = doc
+        .get_mut("build")
+        .and_then(|b| b.as_table_like_mut())
+        .and_then(|b| b.get_mut("target"))
+    {
+        process_build_target(build_target, prune_build_targets, filter, &mut report);
+    }
+
+    let output = doc.to_string();
+    report.changed = output != content;
+    Ok((output, report))
+}
+
+fn process_build_target(
+    item: &mut Item,
+    prune: bool,
+    filter: &TargetFilter,
+    report: &mut CargoConfigReport,
+) {
+    let kept = |triple: &str| !should_remove_target_config(triple, filter);
+    match item.as_value_mut() {
+        // target = "x86_64-pc-windows-msvc"
+        Some(Value::String(triple)) => {
+            let triple = triple.value();
+            if !kept(triple) {
+                report
+                    .warnings
+                    .push(format!("build.target 未配置保留的目标: {}", triple));
+            }
+        }
+        // target = ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"]
+        Some(Value::Array(array)) => {
+            let triples: Vec<&str> = array.iter().filter_map(|v| v.as_str()).collect();
+            let removed: Vec<String> = triples
+                .iter()
+                .filter(|t| !kept(t))
+                .map(|t| t.to_string())
+                .collect();
+
+            if removed.len() == triples.len() {
+                // 全部是不保留的目标时只提示，不修改，避免留下空数组
+                if !triples.is_empty() {
+                    report.warnings.push(format!(
+                        "build.target 未配置保留的目标: {}",
+                        removed.join(", ")
+                    ));
+                }
+                return;
+            }
+
+            if prune && !removed.is_empty() {
+                array.retain(|v| v.as_str().is_none_or(kept));
+                report.removed_build_targets = removed;
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,462 @@
+//! `cfg(...)` 表达式的解析与求值
+//!
+//! 按 Cargo 的语法把 `cfg(all(unix, not(target_os = "macos")))` 这类表达式解析为 [`CfgExpr`]，
+//! 再针对保留的目标平台求值，而不是在字符串中查找关键字。
+
+use crate::TargetFilter;
+use cfg_expr::targets::{get_builtin_target_by_triple, Endian, TargetInfo, ALL_BUILTINS};
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+
+// 内置目标中出现过的 target_os 值（加上裸机的 "none"），每个进程只构建一次
+static KNOWN_OS: LazyLock<BTreeSet<&'static str>> = LazyLock::new(|| {
+    ALL_BUILTINS
+        .iter()
+        .map(|target| target.os.as_ref().map_or("none", |os| os.as_str()))
+        .collect()
+});
+
+/// 解析后的 cfg 表达式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgExpr {
+    /// `all(...)`，没有操作数时为真
+    All(Vec<CfgExpr>),
+    /// `any(...)`，没有操作数时为假
+    Any(Vec<CfgExpr>),
+    /// `not(...)`
+    Not(Box<CfgExpr>),
+    /// 单独的标识符，例如 `unix`、`test`
+    Name(String),
+    /// 键值对，例如 `target_os = "linux"`
+    KeyValue(String, String),
+}
+
+/// cfg 表达式解析错误，位置为字节偏移
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgError {
+    /// 表达式意外结束
+    UnexpectedEnd,
+    /// 无法识别的字符
+    UnexpectedChar(char, usize),
+    /// 字符串没有闭合引号
+    UnterminatedString(usize),
+    /// 期望的内容（例如 `)`、字符串）没有出现
+    Expected(&'static str, usize),
+    /// 未知的函数，只有 `all`、`any`、`not`
+    UnknownFunction(String, usize),
+    /// `not(...)` 必须正好有一个操作数
+    NotArity(usize),
+}
+
+impl std::fmt::Display for CfgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CfgError::UnexpectedEnd => write!(f, "cfg 表达式意外结束"),
+            CfgError::UnexpectedChar(c, pos) => write!(f, "位置 {} 有无法识别的字符 {:?}", pos, c),
+            CfgError::UnterminatedString(pos) => write!(f, "位置 {} 的字符串没有闭合", pos),
+            CfgError::Expected(what, pos) => write!(f, "位置 {} 应为 {}", pos, what),
+            CfgError::UnknownFunction(name, pos) => {
+                write!(
+                    f,
+                    "位置 {} 有未知的函数 {}（只支持 all、any、not）",
+                    pos, name
+                )
+            }
+            CfgError::NotArity(pos) => write!(f, "位置 {} 的 not() 必须正好有一个操作数", pos),
+        }
+    }
+}
+
+impl std::error::Error for CfgError {}
+
+/// 解析 cfg 表达式，接受 `cfg(...)` 形式或不带 `cfg` 的单个谓词（例如 `target_os = "linux"`）
+pub fn parse(input: &str) -> Result<CfgExpr, CfgError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        end: input.len(),
+    };
+
+    let is_cfg_call = matches!(
+        tokens.as_slice(),
+        [(Token::Ident(name), _), (Token::LParen, _), ..] if name == "cfg"
+    );
+    let expr = if is_cfg_call {
+        parser.pos = 2;
+        let expr = parser.predicate()?;
+        parser.expect(Token::RParen, ")")?;
+        expr
+    } else {
+        parser.predicate()?
+    };
+
+    match parser.peek() {
+        None => Ok(expr),
+        Some((_, pos)) => Err(CfgError::Expected("表达式结束", pos)),
+    }
+}
+
+impl CfgExpr {
+    /// 表达式是否可能在保留的目标平台上成立
+    ///
+    /// 对 [`TargetFilter`] 保留的每个目标求值（`preset` 中的目标，默认是 x86_64 和 riscv64 的
+    /// Linux 目标；设置了 `keep_triples` 时是其中的 triple），任一目标上不为假即返回 true。
+    /// 与平台无关的条件（`test`、`debug_assertions`、`target_feature`、未设置 `active_features`
+    /// 时的 `feature` 以及自定义标志）和不认识的 `target_os` 值（例如 `"windows_subsystem_helper"`）
+    /// 视为未知：未知不会让表达式为假，`not(未知)` 仍是未知。
+    /// 因此只有能确定在所有保留目标上都不成立的表达式才返回 false
+    pub fn matches(&self, filter: &TargetFilter) -> bool {
+        if filter.keep_triples.is_empty() {
+            return filter
+                .preset_targets()
+                .iter()
+                .any(|target| self.eval(target, filter) != Truth::False);
+        }
+        filter
+            .keep_triples
+            .iter()
+            .any(|triple| match get_builtin_target_by_triple(triple) {
+                Some(target) => self.eval(target, filter) != Truth::False,
+                // 不认识的 triple 无法判断，保留
+                None => true,
+            })
+    }
+
+    fn eval(&self, target: &TargetInfo, filter: &TargetFilter) -> Truth {
+        match self {
+            CfgExpr::All(exprs) => exprs.iter().fold(Truth::True, |acc, expr| {
+                match (acc, expr.eval(target, filter)) {
+                    (Truth::False, _) | (_, Truth::False) => Truth::False,
+                    (Truth::Unknown, _) | (_, Truth::Unknown) => Truth::Unknown,
+                    _ => Truth::True,
+                }
+            }),
+            CfgExpr::Any(exprs) => exprs.iter().fold(Truth::False, |acc, expr| {
+                match (acc, expr.eval(target, filter)) {
+                    (Truth::True, _) | (_, Truth::True) => Truth::True,
+                    (Truth::Unknown, _) | (_, Truth::Unknown) => Truth::Unknown,
+                    _ => Truth::False,
+                }
+            }),
+            CfgExpr::Not(expr) => match expr.eval(target, filter) {
+                Truth::True => Truth::False,
+                Truth::False => Truth::True,
+                Truth::Unknown => Truth::Unknown,
+            },
+            CfgExpr::Name(name) => match name.as_str() {
+                "unix" | "windows" => {
+                    Truth::from(target.families.iter().any(|f| f.as_str() == name))
+                }
+                _ => Truth::Unknown,
+            },
+            CfgExpr::KeyValue(key, value) => eval_key_value(key, value, target, filter),
+        }
+    }
+}
+
+/// 三值逻辑：与平台无关的条件为 Unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Truth {
+    True,
+    False,
+    Unknown,
+}
+
+impl From<bool> for Truth {
+    fn from(value: bool) -> Self {
+        if value {
+            Truth::True
+        } else {
+            Truth::False
+        }
+    }
+}
+
+fn eval_key_value(key: &str, value: &str, target: &TargetInfo, filter: &TargetFilter) -> Truth {
+    let matches = match key {
+        // 没有操作系统的目标（裸机）在 rustc 中是 target_os = "none"；
+        // 不是内置目标的操作系统无法判断（可能是自定义目标），视为未知
+        "target_os" if !KNOWN_OS.contains(value) => return Truth::Unknown,
+        "target_os" => target.os.as_ref().map_or("none", |os| os.as_str()) == value,
+        "target_family" => target.families.iter().any(|f| f.as_str() == value),
+        "target_arch" => target.arch.as_str() == value,
+        "target_env" => target.env.as_ref().map_or("", |env| env.as_str()) == value,
+        "target_vendor" => {
+            target
+                .vendor
+                .as_ref()
+                .map_or("unknown", |vendor| vendor.as_str())
+                == value
+        }
+        "target_abi" => target.abi.as_ref().map_or("", |abi| abi.as_str()) == value,
+        "target_pointer_width" => target.pointer_width.to_string() == value,
+        "target_endian" => {
+            let endian = match target.endian {
+                Endian::big => "big",
+                Endian::little => "little",
+            };
+            endian == value
+        }
+        "feature" => match &filter.active_features {
+            Some(features) => features.contains(value),
+            None => return Truth::Unknown,
+        },
+        _ => return Truth::Unknown,
+    };
+    Truth::from(matches)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    LParen,
+    RParen,
+    Comma,
+    Eq,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, CfgError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' | '=' => {
+                chars.next();
+                let token = match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    ',' => Token::Comma,
+                    _ => Token::Eq,
+                };
+                tokens.push((token, pos));
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(CfgError::UnterminatedString(pos)),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(CfgError::UnterminatedString(pos)),
+                    }
+                }
+                tokens.push((Token::Str(value), pos));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((Token::Ident(ident), pos));
+            }
+            c => return Err(CfgError::UnexpectedChar(c, pos)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Token, usize)],
+    pos: usize,
+    /// 输入长度，用于报告意外结束的位置
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<(&Token, usize)> {
+        self.tokens.get(self.pos).map(|(token, pos)| (token, *pos))
+    }
+
+    fn next(&mut self) -> Option<(&Token, usize)> {
+        let token = self.tokens.get(self.pos).map(|(token, pos)| (token, *pos));
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &'static str) -> Result<(), CfgError> {
+        match self.next() {
+            Some((token, _)) if *token == expected => Ok(()),
+            Some((_, pos)) => Err(CfgError::Expected(what, pos)),
+            None => Err(CfgError::Expected(what, self.end)),
+        }
+    }
+
+    fn predicate(&mut self) -> Result<CfgExpr, CfgError> {
+        let (name, pos) = match self.next() {
+            Some((Token::Ident(name), pos)) => (name.clone(), pos),
+            Some((_, pos)) => return Err(CfgError::Expected("标识符", pos)),
+            None => return Err(CfgError::UnexpectedEnd),
+        };
+
+        match self.peek() {
+            Some((Token::LParen, _)) => {
+                self.pos += 1;
+                let args = self.list()?;
+                match name.as_str() {
+                    "all" => Ok(CfgExpr::All(args)),
+                    "any" => Ok(CfgExpr::Any(args)),
+                    "not" => {
+                        let [arg]: [CfgExpr; 1] =
+                            args.try_into().map_err(|_| CfgError::NotArity(pos))?;
+                        Ok(CfgExpr::Not(Box::new(arg)))
+                    }
+                    _ => Err(CfgError::UnknownFunction(name, pos)),
+                }
+            }
+            Some((Token::Eq, _)) => {
+                self.pos += 1;
+                match self.next() {
+                    Some((Token::Str(value), _)) => Ok(CfgExpr::KeyValue(name, value.clone())),
+                    Some((_, pos)) => Err(CfgError::Expected("字符串", pos)),
+                    None => Err(CfgError::Expected("字符串", self.end)),
+                }
+            }
+            _ => Ok(CfgExpr::Name(name)),
+        }
+    }
+
+    /// 左括号之后、逗号分隔的操作数列表（允许末尾逗号），消费右括号
+    fn list(&mut self) -> Result<Vec<CfgExpr>, CfgError> {
+        let mut args = Vec::new();
+        loop {
+            if let Some((Token::RParen, _)) = self.peek() {
+                self.pos += 1;
+                return Ok(args);
+            }
+            args.push(self.predicate()?);
+            match self.next() {
+                Some((Token::Comma, _)) => {}
+                Some((Token::RParen, _)) => return Ok(args),
+                Some((_, pos)) => return Err(CfgError::Expected(", 或 )", pos)),
+                None => return Err(CfgError::Expected(")", self.end)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> CfgExpr {
+        CfgExpr::Name(name.to_string())
+    }
+
+    fn key_value(key: &str, value: &str) -> CfgExpr {
+        CfgExpr::KeyValue(key.to_string(), value.to_string())
+    }
+
+    fn removed(input: &str) -> bool {
+        !parse(input).unwrap().matches(&TargetFilter::default())
+    }
+
+    /// 多层嵌套的 all/any/not 按结构解析，允许末尾逗号
+    #[test]
+    fn parses_nested_expressions() {
+        assert_eq!(
+            parse(r#"cfg(all(any(not(windows), target_os = "linux",), not(any(test, unix,)),))"#),
+            Ok(CfgExpr::All(vec![
+                CfgExpr::Any(vec![
+                    CfgExpr::Not(Box::new(name("windows"))),
+                    key_value("target_os", "linux"),
+                ]),
+                CfgExpr::Not(Box::new(CfgExpr::Any(vec![name("test"), name("unix")]))),
+            ]))
+        );
+        assert_eq!(
+            parse(r#"cfg(not(all(any(all(not(not(unix)))))))"#),
+            Ok(CfgExpr::Not(Box::new(CfgExpr::All(vec![CfgExpr::Any(
+                vec![CfgExpr::All(vec![CfgExpr::Not(Box::new(CfgExpr::Not(
+                    Box::new(name("unix"))
+                )))])]
+            )]))))
+        );
+        assert_eq!(parse("cfg(any())"), Ok(CfgExpr::Any(vec![])));
+        // 不带 cfg 的单个谓词，字符串中的转义引号
+        assert_eq!(
+            parse(r#"target_os = "linux""#),
+            Ok(key_value("target_os", "linux"))
+        );
+        assert_eq!(
+            parse(r#"cfg(feature = "a\"b")"#),
+            Ok(key_value("feature", "a\"b"))
+        );
+    }
+
+    /// 每种错误都带出错位置的字节偏移
+    #[test]
+    fn reports_error_offsets() {
+        assert_eq!(parse(""), Err(CfgError::UnexpectedEnd));
+        assert_eq!(parse("cfg("), Err(CfgError::UnexpectedEnd));
+        assert_eq!(parse("cfg(unix; )"), Err(CfgError::UnexpectedChar(';', 8)));
+        assert_eq!(
+            parse(r#"cfg(target_os = "linux)"#),
+            Err(CfgError::UnterminatedString(16))
+        );
+        assert_eq!(
+            parse("cfg(target_os = linux)"),
+            Err(CfgError::Expected("字符串", 16))
+        );
+        assert_eq!(
+            parse("cfg(target_os ="),
+            Err(CfgError::Expected("字符串", 15))
+        );
+        assert_eq!(parse("cfg(all(unix"), Err(CfgError::Expected(")", 12)));
+        assert_eq!(
+            parse("cfg(unix) x"),
+            Err(CfgError::Expected("表达式结束", 10))
+        );
+        assert_eq!(
+            parse("cfg(none(unix))"),
+            Err(CfgError::UnknownFunction("none".to_string(), 4))
+        );
+        assert_eq!(parse("cfg(not(unix, windows))"), Err(CfgError::NotArity(4)));
+    }
+
+    /// 按完整的值比较，不在字符串中查找关键字
+    #[test]
+    fn matches_values_not_substrings() {
+        assert!(removed(r#"cfg(target_os = "windows")"#));
+        assert!(!removed(r#"cfg(target_os = "windows_subsystem_helper")"#));
+        assert!(!removed(r#"cfg(target_os = "linux")"#));
+        assert!(removed(r#"cfg(all(unix, not(target_os = "linux")))"#));
+        assert!(!removed(
+            r#"cfg(any(windows, all(unix, target_arch = "x86_64")))"#
+        ));
+    }
+
+    /// 与平台无关的条件是未知：not(未知) 仍是未知，只有和确定为假的条件组合时才删除
+    #[test]
+    fn unknown_conditions_are_three_valued() {
+        assert!(!removed("cfg(test)"));
+        assert!(!removed("cfg(not(test))"));
+        assert!(!removed("cfg(not(any(test, windows)))"));
+        assert!(removed("cfg(all(windows, not(test)))"));
+        assert!(!removed("cfg(any(windows, not(debug_assertions)))"));
+
+        let filter = TargetFilter {
+            active_features: Some(["a".to_string()].into()),
+            ..TargetFilter::default()
+        };
+        assert!(parse(r#"cfg(not(feature = "b"))"#)
+            .unwrap()
+            .matches(&filter));
+        assert!(!parse(r#"cfg(not(feature = "a"))"#)
+            .unwrap()
+            .matches(&filter));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::Operation;
+use toml_edit::{DocumentMut, Item, Key, Table};
+
+/// 注释块的插入位置
+enum Anchor {
+    /// 文档位置在此之后的第一个表头之前（None 表示第一个表头之前），没有这样的表头时放在文档末尾
+    AfterPosition(Option<isize>),
+    /// `[features]` 中这个 feature 之前
+    BeforeFeature(String),
+}
+
+/// 注释块：插入位置、在原文档中的排序键（所在表头的位置，feature 在表中的序号）和注释文本
+type Block = (Anchor, (Option<isize>, usize), String);
+
+/// 把整个删除的 target 配置和 feature 以注释的形式写回文档，见 [`crate::StripMode::Comment`]
+///
+/// `original` 为处理前的文档，被删除的内容从中取出并按原格式渲染，每行加上 `#` 前缀（原本就是注释的行和空行不变），
+/// 写入删除位置之后的第一个表头、feature 或文档末尾的 decor 中，输出仍是有效的 TOML。
+/// 只删除了部分依赖的 target 配置和 feature 中被删除的条目不写回
+pub(crate) fn comment_out_removed(
+    doc: &mut DocumentMut,
+    original: &DocumentMut,
+    operations: &[Operation],
+) {
+    let mut blocks: Vec<Block> = Vec::new();
+    for op in operations {
+        match op {
+            Operation::RemoveTarget(key) => {
+                if let Some(block) = removed_target(original, key) {
+                    blocks.push(block);
+                }
+            }
+            Operation::RemoveFeature(name) => {
+                if let Some(block) = removed_feature(doc, original, name) {
+                    blocks.push(block);
+                }
+            }
+            _ => {}
+        }
+    }
+    // 同一位置的多个注释块按原文档中的顺序排列
+    blocks.sort_by_key(|(_, position, _)| *position);
+
+    let tables = visible_tables(doc.as_table());
+    let mut by_table: Vec<(Option<Vec<Step>>, String)> = Vec::new();
+    let mut by_feature: Vec<(String, String)> = Vec::new();
+    for (anchor, _, text) in blocks {
+        match anchor {
+            Anchor::AfterPosition(after) => {
+                let path = tables
+                    .iter()
+                    .find(|(position, _)| after.is_none_or(|after| *position > after))
+                    .map(|(_, path)| path.clone());
+                match by_table.iter_mut().find(|(p, _)| *p == path) {
+                    Some((_, existing)) => existing.push_str(&text),
+                    None => by_table.push((path, text)),
+                }
+            }
+            Anchor::BeforeFeature(name) => match by_feature.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, existing)) => existing.push_str(&text),
+                None => by_feature.push((name, text)),
+            },
+        }
+    }
+
+    for (name, text) in by_feature {
+        if let Some(mut key) = doc
+            .get_mut("features")
+            .and_then(Item::as_table_mut)
+            .and_then(|features| features.key_mut(&name))
+        {
+            let decor = key.leaf_decor_mut();
+            let prefix = decor.prefix().and_then(|p| p.as_str()).unwrap_or("");
+            let prefix = format!("{}{}", text, prefix);
+            decor.set_prefix(prefix);
+        }
+    }
+    for (path, text) in by_table {
+        match path.and_then(|path| table_at_mut(doc.as_table_mut(), &path)) {
+            Some(table) => {
+                let decor = table.decor_mut();
+                let prefix = decor.prefix().and_then(|p| p.as_str()).unwrap_or("\n");
+                let prefix = format!("{}{}", text, prefix);
+                decor.set_prefix(prefix);
+            }
+            None => {
+                let trailing = doc.trailing().as_str().unwrap_or("").to_string();
+                doc.set_trailing(format!("{}{}", trailing, text));
+            }
+        }
+    }
+}
+
+/// 被删除的 `[target]` 下的一项（或顶层的 `target.xxx` 键）：插入位置、排序位置和注释文本
+fn removed_target(original: &DocumentMut, key: &str) -> Option<Block> {
+    let targets = original.get("target");
+    let mut rendered = DocumentMut::new();
+    let position = match targets
+        .and_then(Item::as_table_like)
+        .and_then(|t| t.get_key_value(key))
+    {
+        Some((key, item)) => {
+            let container = targets.and_then(Item::as_table);
+            let header_position = first_position(item);
+            match container {
+                // [target] 表头下的键值：只写回这一行，放在 [target] 表之后
+                Some(table) if header_position.is_none() && !table.is_dotted() => {
+                    rendered.as_table_mut().insert_formatted(key, item.clone());
+                    table.position()
+                }
+                _ => {
+                    // 表头形式的配置原样写回；根表中的点分键和内联表写成 target.xxx = ... 的点分键
+                    let mut key = key.clone();
+                    let mut target = Table::new();
+                    target.set_implicit(true);
+                    if container.is_none_or(Table::is_dotted) {
+                        target.set_dotted(true);
+                        if container.is_none() {
+                            key.leaf_decor_mut().clear();
+                        }
+                    }
+                    target.insert_formatted(&key, item.clone());
+                    rendered.insert("target", Item::Table(target));
+                    header_position
+                }
+            }
+        }
+        None => {
+            let (key, item) = original.as_table().get_key_value(key)?;
+            rendered.as_table_mut().insert_formatted(key, item.clone());
+            first_position(item)
+        }
+    };
+    Some((
+        Anchor::AfterPosition(position),
+        (position, 0),
+        comment_lines(&rendered.to_string()),
+    ))
+}
+
+/// 被删除的 feature：`[features]` 中后面还有保留的 feature 时放在它之前，否则放在 `[features]` 表之后；
+/// 所有 feature 都被删除（`[features]` 表已删除）时连同表头一起写回
+fn removed_feature(doc: &DocumentMut, original: &DocumentMut, name: &str) -> Option<Block> {
+    let features = original.get("features").and_then(Item::as_table)?;
+    let position = features.position();
+    let Some(remaining) = doc.get("features").and_then(Item::as_table) else {
+        // 只在删除第一个 feature 时写回整个表
+        let first_removed = features
+            .iter()
+            .next()
+            .is_some_and(|(first, _)| first == name);
+        if !first_removed {
+            return None;
+        }
+        let mut rendered = DocumentMut::new();
+        rendered.insert("features", Item::Table(features.clone()));
+        return Some((
+            Anchor::AfterPosition(position),
+            (position, 0),
+            comment_lines(&rendered.to_string()),
+        ));
+    };
+
+    let (key, item) = features.get_key_value(name)?;
+    let index = features.iter().position(|(feature, _)| feature == name)?;
+    let mut rendered = DocumentMut::new();
+    rendered.as_table_mut().insert_formatted(key, item.clone());
+    let text = comment_lines(&rendered.to_string());
+
+    let next = features
+        .iter()
+        .map(|(name, _)| name)
+        .skip_while(|feature| *feature != name)
+        .skip(1)
+        .find(|feature| remaining.contains_key(feature));
+    let anchor = match next {
+        Some(next) => Anchor::BeforeFeature(next.to_string()),
+        None => Anchor::AfterPosition(position),
+    };
+    Some((anchor, (position, index), text))
+}
+
+/// 每行加上 `#` 前缀，空行和原本就是注释的行不变
+fn comment_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                format!("{}\n", line)
+            } else {
+                format!("# {}\n", line)
+            }
+        })
+        .collect()
+}
+
+/// 项中表头的最小文档位置
+fn first_position(item: &Item) -> Option<isize> {
+    match item {
+        Item::Table(table) => {
+            let own = table
+                .position()
+                .filter(|_| !table.is_implicit() && !table.is_dotted());
+            table
+                .iter()
+                .filter_map(|(_, item)| first_position(item))
+                .chain(own)
+                .min()
+        }
+        Item::ArrayOfTables(array) => array.iter().filter_map(Table::position).min(),
+        _ => None,
+    }
+}
+
+/// 从根表到某个表的路径，数组表带下标
+type Step = (Key, Option<usize>);
+
+/// 文档中输出表头的表，按输出顺序排列：(文档位置, 路径)
+///
+/// 与 toml_edit 输出时的排序一致，没有位置的表（新插入的表）使用前一个表的位置
+fn visible_tables(root: &Table) -> Vec<(isize, Vec<Step>)> {
+    fn visit(
+        table: &Table,
+        path: &mut Vec<Step>,
+        last: &mut isize,
+        out: &mut Vec<(isize, Vec<Step>)>,
+    ) {
+        if table.is_dotted() {
+            return;
+        }
+        if let Some(position) = table.position() {
+            *last = position;
+        }
+        let visible = !(table.is_implicit() && table.get_values().is_empty())
+            || path.last().is_some_and(|(_, index)| index.is_some());
+        if !path.is_empty() && visible {
+            out.push((*last, path.clone()));
+        }
+        for (key, item) in table.iter() {
+            let key = table.key(key).cloned().unwrap_or_else(|| Key::new(key));
+            match item {
+                Item::Table(child) => {
+                    path.push((key, None));
+                    visit(child, path, last, out);
+                    path.pop();
+                }
+                Item::ArrayOfTables(array) => {
+                    for (index, child) in array.iter().enumerate() {
+                        path.push((key.clone(), Some(index)));
+                        visit(child, path, last, out);
+                        path.pop();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    visit(root, &mut Vec::new(), &mut 0, &mut out);
+    out.sort_by_key(|(position, _)| *position);
+    out
+}
+
+fn table_at_mut<'a>(mut table: &'a mut Table, path: &[Step]) -> Option<&'a mut Table> {
+    for (key, index) in path {
+        let item = table.get_mut(key.get())?;
+        table = match index {
+            None => item.as_table_mut()?,
+            Some(index) => item.as_array_of_tables_mut()?.get_mut(*index)?,
+        };
+    }
+    Some(table)
+}
@@ -0,0 +1,319 @@
+use crate::{ConflictResolution, FeatureMode, KeepTarget, ProcessError, StripMode};
+use std::fs;
+use toml_edit::{DocumentMut, Item};
+
+/// `pre.toml` 配置文件的默认文件名
+pub const CONFIG_FILE_NAME: &str = "pre.toml";
+
+/// `pre init-config` 生成的配置模板，列出所有选项及其默认值
+const CONFIG_TEMPLATE: &str = r##"# pre 配置文件
+#
+# 所有选项均为可选，下面列出的值即为默认值。
+# 通过 `pre --config pre.toml <input.toml>` 使用。
+
+# 处理 .cargo/config.toml 时，从 [build] target 数组中移除非 Linux triple
+# （全部为非 Linux 目标时只给出警告，不修改）
+prune-build-targets = false
+
+# 保留的平台（对应 --keep 和 --platform）：
+#   linux       Linux（x86_64 和 riscv64）
+#   windows     Windows（所有架构）
+#   macos       Apple 平台（macOS、iOS 等，target_vendor = "apple"）
+#   wasm        WASM（wasm32-unknown-unknown、wasi 和 emscripten）
+#   rustc-host  当前工具链的默认目标（rustc -vV 输出的 host）
+#   bare-metal  裸机目标（target_os = "none"，例如 thumbv7em-none-eabihf）
+keep = "linux"
+
+# 按名称推测删除顶层的平台特定依赖（对应 --aggressive），可能误删
+aggressive = false
+
+# 已知平台依赖列表的修改文件（对应 --known-deps），按顺序合并，后面的优先；
+# 命令行的 --known-deps 在这些文件之后合并。文件每行一个依赖名，!name 表示移除
+# known-deps = ["org-known-deps.txt", "team-known-deps.txt"]
+
+# 不删除的依赖（对应 --keep-dep），用于被误判为平台特定的跨平台依赖；
+# 只声明在被删除的 target 配置中时移到顶层依赖表，features 中对它们的引用保留
+# keep-deps = ["windows-helper"]
+
+# 有 feature 条目只因已知平台依赖列表被删除时报错（对应 --no-speculative），
+# 用于只接受有 target 配置作为依据的删除
+no-speculative = false
+
+# 在输出开头添加 "# Generated by pre ..." 生成标记（对应 --header）
+header = false
+
+# 处理后没有剩余依赖和 target 配置时报错（对应 --require-content），
+# 用于发现误处理的平台专属 crate
+require-content = false
+
+# 处理后校验输出仍是有效的 Cargo 清单，features 中没有新出现的悬空引用，
+# 且再处理一次结果不变（对应 --validate）
+validate = false
+
+# 直接覆盖输出文件（对应 --no-atomic）。默认先写入同一目录下的临时文件再重命名覆盖，
+# 中途被终止也不会留下写了一半的文件；用于不支持重命名覆盖的文件系统
+no-atomic = false
+
+# 删除的依赖或 target 配置超过总数的这一比例（0 到 1）时报错（对应 --max-removal-ratio），
+# 用于发现误处理的文件；命令行的 --force 可以跳过检查。默认不检查
+# max-removal-ratio = 0.5
+
+# 依赖同时出现在保留和删除的 target 配置下时的处理方式（对应 --conflict-resolution）：
+#   keep-wins    保留优先，依赖和 features 中的引用都保留
+#   remove-wins  删除优先，从保留的 target 配置中一并删除，并清理 features 中的引用
+conflict-resolution = "keep-wins"
+
+# features 清理方式（对应 --feature-cleaning）：
+#   apply       删除引用了被删除依赖的条目
+#   report-only 只报告将被删除的条目，不修改 features
+#   skip        不处理 features
+feature-cleaning = "apply"
+
+# 被删除的 target 配置和 feature 的处理方式（comment 对应 --comment-out）：
+#   delete   直接删除
+#   comment  改为 # 开头的注释行保留在原位置附近，便于审阅
+strip-mode = "delete"
+
+# 删除对清理后变为空数组的 feature 的引用（对应 --prune-empty-features），
+# 例如 win = ["dep:windows-sys"] 被清空后，default = ["win"] 变为 default = []
+prune-empty-features = false
+
+# 删除清理后变为空数组的 feature 本身，并删除对它们的引用（对应 --remove-empty-features）
+remove-empty-features = false
+
+# 被删除的 target 配置中有 dev-dependencies 时保留它们（对应 --keep-dev-deps），
+# 只删除其中的 dependencies 和 build-dependencies
+keep-dev-deps = false
+
+# 只在被删除的 target 配置中声明、又不是已知平台依赖的依赖移到顶层依赖表（对应 --hoist-deps）
+hoist-deps = false
+
+# [[bin]]、[[example]] 等的 required-features 引用了已删除的 feature 或依赖时删除整个条目
+# （对应 --remove-unbuildable-targets），默认只删除这些引用
+remove-unbuildable-targets = false
+
+# 删除 [patch.<source>] 和 [replace] 中指向已删除依赖的条目（对应 --prune-patches）；
+# 间接依赖仍可能使用这些 patch，默认保留
+prune-patches = false
+
+# 只保留能在这些 triple 上启用的 target 配置（对应 --keep-target），与 keep 解析出的 triple 合并
+# keep-target = ["x86_64-unknown-linux-gnu", "aarch64-unknown-linux-musl"]
+
+# 只保留这些架构的目标（对应 --keep-arch），只在其他架构上启用的 target 配置被删除
+# 未设置时 Linux 保留 x86_64 和 riscv64
+# keep-arch = ["x86_64"]
+
+# 只保留这些环境（target_env）的目标（对应 --keep-env），例如 cfg(target_env = "msvc") 的配置被删除
+# 未设置时保留预设中所有环境的目标
+# keep-env = ["gnu", "musl"]
+
+# 只保留这些操作系统（target_os）的目标（对应 --keep-os），代替 keep 的预设，
+# 未设置 keep-arch 时包括这些系统的所有架构；在其中任一系统上成立的 cfg 表达式都保留
+# keep-os = ["linux", "android"]
+
+# 只保留这组 features 能启用的 target 配置（对应 --active-features）
+# 未设置时 cfg(feature = "...") 条件始终视为未启用
+# active-features = ["std"]
+"##;
+
+/// 命令行工具的配置（对应 `pre.toml`）
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    /// 从 `[build] target` 中移除非 Linux triple
+    pub prune_build_targets: bool,
+    /// 在输出开头添加生成标记注释
+    pub header: bool,
+    /// 处理后没有剩余依赖和 target 配置时报错
+    pub require_content: bool,
+    /// 处理后校验输出仍是有效的 Cargo 清单
+    pub validate: bool,
+    /// 直接覆盖输出文件，不经过临时文件
+    pub no_atomic: bool,
+    /// 删除比例上限，见 [`crate::Processor::max_removal_ratio`]
+    pub max_removal_ratio: Option<f64>,
+    /// 按名称推测删除顶层的平台特定依赖
+    pub aggressive: bool,
+    /// 删除对清理后变为空数组的 feature 的引用
+    pub prune_empty_features: bool,
+    /// 删除清理后变为空数组的 feature
+    pub remove_empty_features: bool,
+    /// 保留被删除的 target 配置中的 dev-dependencies
+    pub keep_dev_deps: bool,
+    /// 把只在被删除的 target 配置中声明的非平台依赖移到顶层
+    pub hoist_deps: bool,
+    /// required-features 引用了已删除的 feature 或依赖时删除整个 target 条目
+    pub remove_unbuildable_targets: bool,
+    /// 删除指向已删除依赖的 `[patch]` 和 `[replace]` 条目
+    pub prune_patches: bool,
+    /// 已知平台依赖列表的修改文件，见 [`crate::KnownDeps`]
+    pub known_deps: Vec<String>,
+    /// 不删除的依赖，见 [`crate::Processor::keep_dep`]
+    pub keep_deps: Vec<String>,
+    /// 禁止只凭已知平台依赖列表删除 feature 条目
+    pub no_speculative: bool,
+    /// 保留的平台
+    pub keep: KeepTarget,
+    /// 依赖同时出现在保留和删除的 target 配置下时的处理方式
+    pub conflict_resolution: ConflictResolution,
+    /// features 清理方式
+    pub feature_cleaning: FeatureMode,
+    /// 被删除的 target 配置和 feature 的处理方式
+    pub strip_mode: StripMode,
+    /// 保留的目标 triple，见 [`crate::TargetFilter::keep_triples`]
+    pub keep_target: Vec<String>,
+    /// 保留的架构，见 [`crate::TargetFilter::keep_arch`]
+    pub keep_arch: Option<Vec<String>>,
+    /// 保留的目标环境，见 [`crate::TargetFilter::keep_env`]
+    pub keep_env: Option<Vec<String>>,
+    /// 保留的操作系统，见 [`crate::TargetFilter::keep_os`]
+    pub keep_os: Option<Vec<String>>,
+    /// 启用的 features，见 [`crate::TargetFilter::active_features`]
+    pub active_features: Option<Vec<String>>,
+}
+
+impl Config {
+    /// 带注释的配置模板
+    pub fn template() -> &'static str {
+        CONFIG_TEMPLATE
+    }
+
+    /// 从文件读取配置
+    pub fn from_file(path: &str) -> Result<Config, ProcessError> {
+        let content = fs::read_to_string(path)?;
+        Config::from_toml_str(&content)
+    }
+
+    /// 从 TOML 字符串解析配置，未知的选项会报错
+    pub fn from_toml_str(content: &str) -> Result<Config, ProcessError> {
+        let doc = content.parse::<DocumentMut>()?;
+        let mut config = Config::default();
+
+        for (key, item) in doc.iter() {
+            match key {
+                "prune-build-targets" => config.prune_build_targets = expect_bool(key, item)?,
+                "keep" => config.keep = expect_str(key, item)?.parse()?,
+                "aggressive" => config.aggressive = expect_bool(key, item)?,
+                "known-deps" => config.known_deps = expect_string_array(key, item)?,
+                "keep-deps" => config.keep_deps = expect_string_array(key, item)?,
+                "no-speculative" => config.no_speculative = expect_bool(key, item)?,
+                "prune-empty-features" => config.prune_empty_features = expect_bool(key, item)?,
+                "remove-empty-features" => config.remove_empty_features = expect_bool(key, item)?,
+                "keep-dev-deps" => config.keep_dev_deps = expect_bool(key, item)?,
+                "hoist-deps" => config.hoist_deps = expect_bool(key, item)?,
+                "remove-unbuildable-targets" => {
+                    config.remove_unbuildable_targets = expect_bool(key, item)?
+                }
+                "prune-patches" => config.prune_patches = expect_bool(key, item)?,
+                "header" => config.header = expect_bool(key, item)?,
+                "require-content" => config.require_content = expect_bool(key, item)?,
+                "validate" => config.validate = expect_bool(key, item)?,
+                "no-atomic" => config.no_atomic = expect_bool(key, item)?,
+                "max-removal-ratio" => config.max_removal_ratio = Some(expect_ratio(key, item)?),
+                "conflict-resolution" => {
+                    config.conflict_resolution = expect_str(key, item)?.parse()?
+                }
+                "feature-cleaning" => config.feature_cleaning = expect_str(key, item)?.parse()?,
+                "strip-mode" => config.strip_mode = expect_str(key, item)?.parse()?,
+                "active-features" => config.active_features = Some(expect_string_array(key, item)?),
+                "keep-arch" => config.keep_arch = Some(expect_string_array(key, item)?),
+                "keep-env" => config.keep_env = Some(expect_string_array(key, item)?),
+                "keep-os" => config.keep_os = Some(expect_string_array(key, item)?),
+                "keep-target" => config.keep_target = expect_string_array(key, item)?,
+                _ => {
+                    return Err(ProcessError::ConfigError(format!("未知的配置项: {}", key)));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn expect_string_array(key: &str, item: &Item) -> Result<Vec<String>, ProcessError> {
+    let error = || ProcessError::ConfigError(format!("配置项 {} 必须是字符串数组", key));
+    item.as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|v| v.as_str().map(str::to_string).ok_or_else(error))
+        .collect()
+}
+
+fn expect_ratio(key: &str, item: &Item) -> Result<f64, ProcessError> {
+    item.as_float()
+        .or_else(|| item.as_integer().map(|i| i as f64))
+        .filter(|ratio| (0.0..=1.0).contains(ratio))
+        .ok_or_else(|| ProcessError::ConfigError(format!("配置项 {} 必须是 0 到 1 之间的数", key)))
+}
+
+fn expect_str<'a>(key: &str, item: &'a Item) -> Result<&'a str, ProcessError> {
+    item.as_str()
+        .ok_or_else(|| ProcessError::ConfigError(format!("配置项 {} 必须是字符串", key)))
+}
+
+fn expect_bool(key: &str, item: &Item) -> Result<bool, ProcessError> {
+    item.as_bool()
+        .ok_or_else(|| ProcessError::ConfigError(format!("配置项 {} 必须是布尔值", key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 模板中的键（包括注释掉的示例），去掉注释符号后的行
+    fn example_lines() -> Vec<&'static str> {
+        CONFIG_TEMPLATE
+            .lines()
+            .filter_map(|line| line.strip_prefix("# "))
+            .filter(|line| {
+                line.split_once(" = ").is_some_and(|(key, _)| {
+                    !key.is_empty() && key.chars().all(|c| c.is_ascii_lowercase() || c == '-')
+                })
+            })
+            .collect()
+    }
+
+    /// 模板能被解析，未注释的值就是默认值
+    #[test]
+    fn template_parses_to_default_config() {
+        assert_eq!(
+            Config::from_toml_str(Config::template()).unwrap(),
+            Config::default()
+        );
+    }
+
+    /// 模板中注释掉的示例去掉注释后同样能被解析，且确实改变了对应的选项
+    #[test]
+    fn template_examples_parse() {
+        let examples = example_lines();
+        assert!(!examples.is_empty());
+        for line in &examples {
+            let config = Config::from_toml_str(line).unwrap_or_else(|e| panic!("{}: {}", line, e));
+            assert_ne!(config, Config::default(), "{}", line);
+        }
+        let all = format!("{}\n{}\n", Config::template(), examples.join("\n"));
+        Config::from_toml_str(&all).unwrap();
+    }
+
+    /// Config 的每个字段在模板中都有对应的键，新增选项时不会遗漏模板
+    #[test]
+    fn template_covers_every_option() {
+        let debug = format!("{:?}", Config::default());
+        let fields: Vec<String> = debug
+            .trim_start_matches("Config {")
+            .split(", ")
+            .filter_map(|field| field.trim().split_once(": "))
+            .map(|(name, _)| name.replace('_', "-"))
+            .collect();
+        assert!(fields.len() > 20, "{:?}", fields);
+        let examples = example_lines();
+        for key in fields {
+            let entry = format!("{} = ", key);
+            assert!(
+                CONFIG_TEMPLATE.lines().any(|line| line.starts_with(&entry))
+                    || examples.iter().any(|line| line.starts_with(&entry)),
+                "模板中没有 {}",
+                key
+            );
+        }
+    }
+}
@@ -0,0 +1,130 @@
+/// 行级 unified diff，每个修改块前后保留 3 行上下文
+///
+/// 内容相同时返回空字符串。清单文件通常只有几百行，直接用最长公共子序列计算
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+    if ops.iter().all(|op| matches!(op, Line::Same(..))) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut index = 0;
+    while index < ops.len() {
+        // 找到下一处修改，向前带上上下文
+        let Some(first_change) = ops[index..]
+            .iter()
+            .position(|op| !matches!(op, Line::Same(..)))
+            .map(|offset| index + offset)
+        else {
+            break;
+        };
+        let start = first_change.saturating_sub(CONTEXT).max(index);
+
+        // 修改之间的相同行不超过 2 * CONTEXT 时合并为同一个块
+        let mut end = first_change;
+        let mut same_run = 0;
+        for (offset, op) in ops[first_change..].iter().enumerate() {
+            if matches!(op, Line::Same(..)) {
+                same_run += 1;
+                if same_run > 2 * CONTEXT {
+                    break;
+                }
+            } else {
+                same_run = 0;
+                end = first_change + offset + 1;
+            }
+        }
+        let end = (end + CONTEXT).min(ops.len());
+
+        let hunk = &ops[start..end];
+        let (old_start, new_start) = line_numbers(&ops[..start]);
+        let old_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, Line::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, Line::Removed(_)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_count),
+            range(new_start, new_count)
+        ));
+        for op in hunk {
+            let (prefix, line) = match op {
+                Line::Same(line) => (' ', line),
+                Line::Removed(line) => ('-', line),
+                Line::Added(line) => ('+', line),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+        index = end;
+    }
+    out
+}
+
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    // lcs[i][j] 为 old[i..] 与 new[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] > lcs[i + 1][j]) {
+            ops.push(Line::Added(new[j]));
+            j += 1;
+        } else {
+            ops.push(Line::Removed(old[i]));
+            i += 1;
+        }
+    }
+    ops
+}
+
+/// 块之前已经出现的行数，返回块在旧、新内容中的起始行号（从 1 开始）
+fn line_numbers(before: &[Line]) -> (usize, usize) {
+    let old = before
+        .iter()
+        .filter(|op| !matches!(op, Line::Added(_)))
+        .count();
+    let new = before
+        .iter()
+        .filter(|op| !matches!(op, Line::Removed(_)))
+        .count();
+    (old + 1, new + 1)
+}
+
+/// 块头中的行范围；空范围按惯例写成前一行的行号
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start - 1),
+        1 => start.to_string(),
+        _ => format!("{},{}", start, count),
+    }
+}
@@ -0,0 +1,90 @@
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+/// 以缩进树的形式列出文档的内部结构（表、键、条目类型），用于调试
+///
+/// 键名用 `{:?}` 输出，便于看出引号、空格等差异；表会标注 `implicit`/`dotted`，
+/// 用来判断某个键实际嵌套在哪一层（例如 `target.'cfg(windows)'.dependencies`
+/// 与 `[target."cfg(windows)".dependencies]` 在结构上的区别）
+pub fn dump_document(doc: &DocumentMut) -> String {
+    let mut out = String::new();
+    dump_table(doc.as_table(), 0, &mut out);
+    out
+}
+
+fn dump_table(table: &Table, depth: usize, out: &mut String) {
+    for (key, item) in table.iter() {
+        dump_item(key, item, depth, out);
+    }
+}
+
+fn dump_item(key: &str, item: &Item, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match item {
+        Item::None => out.push_str(&format!("{}{:?}: none\n", indent, key)),
+        Item::Value(value) => dump_value(key, value, depth, out),
+        Item::Table(table) => {
+            let mut flags = Vec::new();
+            if table.is_implicit() {
+                flags.push("implicit");
+            }
+            if table.is_dotted() {
+                flags.push("dotted");
+            }
+            let flags = if flags.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", flags.join(", "))
+            };
+            out.push_str(&format!("{}{:?}: table{}\n", indent, key, flags));
+            dump_table(table, depth + 1, out);
+        }
+        Item::ArrayOfTables(array) => {
+            out.push_str(&format!(
+                "{}{:?}: array-of-tables[{}]\n",
+                indent,
+                key,
+                array.len()
+            ));
+            for (index, table) in array.iter().enumerate() {
+                out.push_str(&format!("{}  [{}]: table\n", indent, index));
+                dump_table(table, depth + 2, out);
+            }
+        }
+    }
+}
+
+fn dump_value(key: &str, value: &Value, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::InlineTable(table) => {
+            out.push_str(&format!("{}{:?}: inline-table\n", indent, key));
+            for (key, value) in table.iter() {
+                dump_value(key, value, depth + 1, out);
+            }
+        }
+        Value::Array(array) => {
+            let items: Vec<String> = array.iter().map(value_summary).collect();
+            out.push_str(&format!(
+                "{}{:?}: array[{}] [{}]\n",
+                indent,
+                key,
+                array.len(),
+                items.join(", ")
+            ));
+        }
+        _ => out.push_str(&format!("{}{:?}: {}\n", indent, key, value_summary(value))),
+    }
+}
+
+/// 标量值显示为 `类型 = 值`，复合值只显示类型
+fn value_summary(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("string = {:?}", s.value()),
+        Value::Integer(i) => format!("integer = {}", i.value()),
+        Value::Float(f) => format!("float = {}", f.value()),
+        Value::Boolean(b) => format!("boolean = {}", b.value()),
+        Value::Datetime(d) => format!("datetime = {}", d.value()),
+        Value::Array(a) => format!("array[{}]", a.len()),
+        Value::InlineTable(_) => "inline-table".to_string(),
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{get_known_platform_deps, ProcessError};
+use std::collections::BTreeSet;
+use std::fs;
+use toml_edit::DocumentMut;
+
+/// 已知平台特定依赖列表，在内置列表上叠加外部文件的修改
+///
+/// 文件每行一个依赖名，`!name` 表示从列表中移除（包括内置列表和之前的文件添加的依赖），
+/// 空行和 `#` 开头的注释行忽略：
+///
+/// ```text
+/// # 组织内部的 Windows 封装
+/// acme-win32
+/// !winapi
+/// ```
+///
+/// 也可以是 TOML 格式，条目写在 `platform-deps` 数组中（同样支持 `!name`）：
+///
+/// ```toml
+/// platform-deps = ["acme-win32", "!winapi"]
+/// ```
+///
+/// 多个文件按顺序合并，后面的文件优先
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KnownDeps {
+    added: BTreeSet<String>,
+    removed: BTreeSet<String>,
+}
+
+impl KnownDeps {
+    /// 只包含内置列表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 合并一个文件，见 [`KnownDeps::merge_str`]
+    pub fn merge_file(&mut self, path: &str) -> Result<(), ProcessError> {
+        let content = fs::read_to_string(path)?;
+        self.merge_str(&content)
+    }
+
+    /// 按顺序应用文本中的添加和移除，覆盖之前的修改
+    ///
+    /// 内容是包含 `platform-deps` 数组的 TOML 时按 TOML 读取，否则按每行一个依赖名读取
+    pub fn merge_str(&mut self, content: &str) -> Result<(), ProcessError> {
+        if let Some(list) = content
+            .parse::<DocumentMut>()
+            .ok()
+            .and_then(|doc| doc.get("platform-deps").cloned())
+        {
+            let error = || ProcessError::ConfigError("platform-deps 必须是字符串数组".to_string());
+            for (index, entry) in list.as_array().ok_or_else(error)?.iter().enumerate() {
+                let entry = entry.as_str().ok_or_else(error)?;
+                self.apply(entry)
+                    .map_err(|e| ProcessError::ConfigError(format!("第 {} 项{}", index + 1, e)))?;
+            }
+            return Ok(());
+        }
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.apply(line)
+                .map_err(|e| ProcessError::ConfigError(format!("第 {} 行{}", index + 1, e)))?;
+        }
+        Ok(())
+    }
+
+    /// 添加依赖名，与文件中的条目一样覆盖之前的移除
+    pub fn extend<I, S>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for name in names {
+            self.add(name.as_ref());
+        }
+    }
+
+    /// 应用一个条目（`name` 或 `!name`），出错时返回错误描述
+    fn apply(&mut self, entry: &str) -> Result<(), String> {
+        let entry = entry.trim();
+        let (remove, name) = match entry.strip_prefix('!') {
+            Some(name) => (true, name.trim()),
+            None => (false, entry),
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("不是有效的依赖名: {}", entry));
+        }
+        if remove {
+            self.added.remove(name);
+            self.removed.insert(name.to_string());
+        } else {
+            self.add(name);
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, name: &str) {
+        self.removed.remove(name);
+        self.added.insert(name.to_string());
+    }
+
+    /// 依赖是否在最终列表中
+    pub fn contains(&self, dep: &str) -> bool {
+        !self.removed.contains(dep)
+            && (self.added.contains(dep) || get_known_platform_deps().contains(dep))
+    }
+
+    /// 最终列表（内置列表加上添加的依赖，去掉移除的依赖）
+    pub fn names(&self) -> BTreeSet<&str> {
+        get_known_platform_deps()
+            .iter()
+            .copied()
+            .chain(self.added.iter().map(String::as_str))
+            .filter(|dep| !self.removed.contains(*dep))
+            .collect()
+    }
+}
@@ -1,14 +1,66 @@
 use cargo_toml::Manifest;
-use cfg_expr::{
-    targets::{get_builtin_target_by_triple, TargetInfo},
-    Expression, Predicate,
-};
-use std::collections::HashSet;
+use cfg_expr::targets::{get_builtin_target_by_triple, TargetInfo};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::LazyLock;
 use toml_edit::{Array, DocumentMut, Item, Value};
+use triple::TripleParts;
+
+/// 启用 `logging` feature 时用 `log::info!` 记录删除决定，未启用时展开为空，参数不会被求值
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::info!($($arg)*);
+    };
+}
+
+/// 与 [`log_info`] 相同，使用 `log::debug!`，用于单个依赖和 feature 条目
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::debug!($($arg)*);
+    };
+}
+
+mod analysis;
+mod cache;
+mod cargo_config;
+pub mod cfg;
+mod comment;
+mod config;
+mod diff;
+mod dump;
+mod known_deps;
+mod ops;
+mod packaging;
+mod patches;
+#[cfg(feature = "path-deps")]
+mod path_deps;
+mod rustc_host;
+mod triple;
+mod walk;
+mod workspace;
 
-// Linux 目标平台列表（仅 x86_64 和 riscv64 架构）
-fn get_linux_targets() -> Vec<&'static TargetInfo> {
+pub use analysis::{analyze_manifest, Analysis, TargetAnalysis};
+pub use cache::Cache;
+pub use cargo_config::{
+    process_cargo_config, process_cargo_config_string, process_cargo_config_string_with_filter,
+    process_cargo_config_with_filter, CargoConfigReport,
+};
+pub use config::{Config, CONFIG_FILE_NAME};
+pub use diff::unified_diff;
+pub use dump::dump_document;
+pub use known_deps::KnownDeps;
+pub use ops::{parse_operations, replay, serialize_operations, Operation};
+pub use rustc_host::{parse_rustc_host, rustc_host_triple};
+pub use walk::find_manifests;
+pub use workspace::process_workspace;
+
+// Linux 目标平台列表（仅 x86_64 和 riscv64 架构），每个进程只构建一次
+static LINUX_TARGETS: LazyLock<Vec<&'static TargetInfo>> = LazyLock::new(|| {
     cfg_expr::targets::ALL_BUILTINS
         .iter()
         .filter(|target| {
@@ -21,6 +73,471 @@ fn get_linux_targets() -> Vec<&'static TargetInfo> {
                 || target.arch == cfg_expr::targets::Arch::riscv64
         })
         .collect()
+});
+
+fn get_linux_targets() -> &'static [&'static TargetInfo] {
+    &LINUX_TARGETS
+}
+
+// 裸机目标平台列表（target_os = "none"，不含 wasm 等有 target_family 的目标）
+static BARE_METAL_TARGETS: LazyLock<Vec<&'static TargetInfo>> = LazyLock::new(|| {
+    cfg_expr::targets::ALL_BUILTINS
+        .iter()
+        .filter(|target| is_bare_metal_target(target))
+        .collect()
+});
+
+fn is_bare_metal_target(target: &TargetInfo) -> bool {
+    target.os.is_none() && target.families.is_empty()
+}
+
+fn get_bare_metal_targets() -> &'static [&'static TargetInfo] {
+    &BARE_METAL_TARGETS
+}
+
+// Windows、Apple 和 WASM 预设的目标列表，包括所有架构
+static WINDOWS_TARGETS: LazyLock<Vec<&'static TargetInfo>> =
+    LazyLock::new(|| builtin_targets_in(TargetPreset::Windows));
+static APPLE_TARGETS: LazyLock<Vec<&'static TargetInfo>> =
+    LazyLock::new(|| builtin_targets_in(TargetPreset::Macos));
+static WASM_TARGETS: LazyLock<Vec<&'static TargetInfo>> =
+    LazyLock::new(|| builtin_targets_in(TargetPreset::Wasm));
+
+fn builtin_targets_in(preset: TargetPreset) -> Vec<&'static TargetInfo> {
+    cfg_expr::targets::ALL_BUILTINS
+        .iter()
+        .filter(|target| preset.contains(target))
+        .collect()
+}
+
+/// 平台过滤条件，决定哪些 target 配置被保留
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TargetFilter {
+    /// 启用的 features
+    ///
+    /// 设置后 `cfg(feature = "x")` 按 x 是否在集合中求值，只保留这组 features 能启用的 target 配置；
+    /// 未设置时 feature 条件视为未知，不会单独导致 target 配置被删除
+    pub active_features: Option<BTreeSet<String>>,
+    /// 保留的目标 triple
+    ///
+    /// 不为空时只保留能在其中任一 triple 上启用的 target 配置：`cfg(...)` 按每个 triple 求值，
+    /// `[target.<triple>]` 只保留列表中的 triple；为空时保留 `preset` 中的目标
+    pub keep_triples: Vec<String>,
+    /// 未设置 `keep_triples` 时保留的一组目标，默认是 Linux（x86_64 和 riscv64）
+    pub preset: TargetPreset,
+    /// 保留的架构（`target_arch` 的取值，例如 `x86_64`）
+    ///
+    /// 设置后 `preset` 中只保留这些架构的目标，Linux 预设从所有 Linux 目标中选取（可以是
+    /// `aarch64` 等默认不保留的架构），只在其他架构上启用的 target 配置和 triple 被删除；
+    /// 未设置时使用预设自身的架构。设置了 `keep_triples` 时不使用
+    pub keep_arch: Option<Vec<String>>,
+    /// 保留的目标环境（`target_env` 的取值，例如 `gnu`、`musl`）
+    ///
+    /// 设置后 `preset` 中只保留这些环境的目标，`cfg(target_env = "msvc")` 等只在其他环境上成立的
+    /// target 配置被删除；没有环境的目标对应空字符串。未设置时保留预设中所有环境的目标。
+    /// 设置了 `keep_triples` 时不使用
+    pub keep_env: Option<Vec<String>>,
+    /// 保留的操作系统（`target_os` 的取值，例如 `linux`、`android`，裸机为 `none`）
+    ///
+    /// 设置后代替 `preset` 选择目标：保留这些系统的目标（未设置 `keep_arch` 时包括所有架构），
+    /// 在其中任一目标上成立的 cfg 表达式都保留，例如 `linux` 和 `android` 时 `cfg(any(target_os = "android",
+    /// target_os = "ios"))` 保留，`cfg(all(target_os = "android", target_os = "ios"))` 删除。
+    /// 设置了 `keep_triples` 时不使用
+    pub keep_os: Option<Vec<String>>,
+}
+
+impl TargetFilter {
+    /// 未设置 `keep_triples` 时求值用的目标列表
+    pub(crate) fn preset_targets(&self) -> Cow<'static, [&'static TargetInfo]> {
+        let targets: Cow<'static, [&'static TargetInfo]> = match (&self.keep_arch, &self.keep_os) {
+            (None, None) => Cow::Borrowed(match self.preset {
+                TargetPreset::Linux => get_linux_targets(),
+                TargetPreset::BareMetal => get_bare_metal_targets(),
+                TargetPreset::Windows => &WINDOWS_TARGETS,
+                TargetPreset::Macos => &APPLE_TARGETS,
+                TargetPreset::Wasm => &WASM_TARGETS,
+            }),
+            (arches, oss) => {
+                let in_preset = |target: &TargetInfo| match oss {
+                    Some(oss) => {
+                        let os = target.os.as_ref().map_or("none", |os| os.as_str());
+                        oss.iter().any(|keep| keep == os)
+                    }
+                    None => self.preset.contains(target),
+                };
+                Cow::Owned(
+                    cfg_expr::targets::ALL_BUILTINS
+                        .iter()
+                        .filter(|target| {
+                            in_preset(target)
+                                && arches.as_ref().is_none_or(|arches| {
+                                    arches.iter().any(|arch| arch == target.arch.as_str())
+                                })
+                        })
+                        .collect(),
+                )
+            }
+        };
+        let Some(envs) = &self.keep_env else {
+            return targets;
+        };
+        Cow::Owned(
+            targets
+                .iter()
+                .copied()
+                .filter(|target| {
+                    let env = target.env.as_ref().map_or("", |env| env.as_str());
+                    envs.iter().any(|keep| keep == env)
+                })
+                .collect(),
+        )
+    }
+
+    /// 未设置 `keep_triples` 时保留的目标 triple；`keep_arch`、`keep_env` 或 `keep_os` 没有匹配的目标时为空
+    pub fn preset_triples(&self) -> Vec<&'static str> {
+        self.preset_targets()
+            .iter()
+            .map(|target| target.triple.as_str())
+            .collect()
+    }
+
+    /// 未设置 `keep_triples` 时 triple 是否保留：内置 triple 按是否在预设的目标列表中判断，
+    /// 不认识的 triple 按 `arch-vendor-os-env` 拆分后判断（见 [`TripleParts`]）：架构按 `target_arch`
+    /// 比较（`riscv64gc` 为 `riscv64`），环境和系统按前缀比较（`gnueabihf` 属于 `gnu`），
+    /// `linux` 不包括 Android 的 triple
+    fn keeps_preset_triple(&self, triple: &str) -> bool {
+        if get_builtin_target_by_triple(triple).is_some() {
+            return self
+                .preset_targets()
+                .iter()
+                .any(|target| target.triple.as_str() == triple);
+        }
+        let parts = TripleParts::parse(triple);
+        let keeps_env = self
+            .keep_env
+            .as_ref()
+            .is_none_or(|envs| envs.iter().any(|env| parts.has_env(env)));
+        let keeps_arch = self.keep_arch.as_ref().is_none_or(|arches| {
+            let target_arch = parts.target_arch();
+            arches
+                .iter()
+                .any(|arch| arch == target_arch || arch == parts.arch)
+        });
+        if !keeps_env || !keeps_arch {
+            return false;
+        }
+        if let Some(oss) = &self.keep_os {
+            return oss.iter().any(|os| parts.has_os(os));
+        }
+        match self.preset {
+            TargetPreset::Linux if self.keep_arch.is_none() => is_linux_target_triple(triple),
+            TargetPreset::Linux => parts.has_os("linux"),
+            TargetPreset::BareMetal => parts.os == Some("none"),
+            TargetPreset::Windows => parts.has_os("windows"),
+            TargetPreset::Macos => parts.vendor == Some("apple"),
+            TargetPreset::Wasm => parts.arch.starts_with("wasm"),
+        }
+    }
+
+    /// 是否只保留 Linux 目标，已知平台依赖列表只在这时使用
+    fn keeps_linux(&self) -> bool {
+        if self.keep_triples.is_empty() {
+            match &self.keep_os {
+                Some(oss) => oss.iter().all(|os| os == "linux"),
+                None => self.preset == TargetPreset::Linux,
+            }
+        } else {
+            self.keep_triples
+                .iter()
+                .all(|triple| is_linux_target_triple(triple))
+        }
+    }
+}
+
+/// 预设的一组保留目标
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetPreset {
+    /// Linux（x86_64 和 riscv64）
+    #[default]
+    Linux,
+    /// 裸机目标（`target_os = "none"`），例如 `thumbv7em-none-eabihf`、`riscv32imac-unknown-none-elf`
+    BareMetal,
+    /// Windows（`target_os = "windows"`，所有架构和环境）
+    Windows,
+    /// Apple 平台（`target_vendor = "apple"`：macOS、iOS、tvOS 等）
+    Macos,
+    /// WASM（`target_family = "wasm"`：`wasm32-unknown-unknown`、wasi 和 emscripten）
+    Wasm,
+}
+
+impl TargetPreset {
+    /// 名称，与 [`KeepTarget`] 的写法相同
+    pub fn name(self) -> &'static str {
+        match self {
+            TargetPreset::Linux => "linux",
+            TargetPreset::BareMetal => "bare-metal",
+            TargetPreset::Windows => "windows",
+            TargetPreset::Macos => "macos",
+            TargetPreset::Wasm => "wasm",
+        }
+    }
+
+    /// 内置目标是否属于这个预设（Linux 为所有架构的 Linux 目标）
+    fn contains(self, target: &TargetInfo) -> bool {
+        use cfg_expr::targets::{Family, Os, Vendor};
+        match self {
+            TargetPreset::Linux => target.os == Some(Os::linux),
+            TargetPreset::BareMetal => is_bare_metal_target(target),
+            TargetPreset::Windows => target.os == Some(Os::windows),
+            TargetPreset::Macos => target.vendor == Some(Vendor::apple),
+            TargetPreset::Wasm => target.families.contains(&Family::wasm),
+        }
+    }
+}
+
+/// 要保留的平台
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeepTarget {
+    /// Linux（x86_64 和 riscv64）
+    #[default]
+    Linux,
+    /// 当前工具链的默认目标，见 [`rustc_host_triple`]
+    RustcHost,
+    /// 裸机目标，见 [`TargetPreset::BareMetal`]
+    BareMetal,
+    /// Windows，见 [`TargetPreset::Windows`]
+    Windows,
+    /// Apple 平台，见 [`TargetPreset::Macos`]
+    Macos,
+    /// WASM，见 [`TargetPreset::Wasm`]
+    Wasm,
+}
+
+impl KeepTarget {
+    /// 解析为 [`TargetFilter::keep_triples`] 中的 triple，预设的平台为 None
+    pub fn resolve(self) -> Result<Option<String>, ProcessError> {
+        match self {
+            KeepTarget::Linux => Ok(None),
+            KeepTarget::RustcHost => rustc_host_triple().map(Some),
+            KeepTarget::BareMetal | KeepTarget::Windows | KeepTarget::Macos | KeepTarget::Wasm => {
+                Ok(None)
+            }
+        }
+    }
+
+    /// 对应的 [`TargetFilter::preset`]，只在没有解析出 triple 时起作用
+    pub fn preset(self) -> TargetPreset {
+        match self {
+            KeepTarget::BareMetal => TargetPreset::BareMetal,
+            KeepTarget::Windows => TargetPreset::Windows,
+            KeepTarget::Macos => TargetPreset::Macos,
+            KeepTarget::Wasm => TargetPreset::Wasm,
+            KeepTarget::Linux | KeepTarget::RustcHost => TargetPreset::Linux,
+        }
+    }
+}
+
+impl std::str::FromStr for KeepTarget {
+    type Err = ProcessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linux" => Ok(KeepTarget::Linux),
+            "rustc-host" => Ok(KeepTarget::RustcHost),
+            "bare-metal" => Ok(KeepTarget::BareMetal),
+            "windows" => Ok(KeepTarget::Windows),
+            "macos" => Ok(KeepTarget::Macos),
+            "wasm" => Ok(KeepTarget::Wasm),
+            _ => Err(ProcessError::ConfigError(format!(
+                "未知的保留平台: {}（可选 linux、windows、macos、wasm、rustc-host、bare-metal）",
+                s
+            ))),
+        }
+    }
+}
+
+/// 同一个依赖同时出现在保留和删除的 target 配置下时的处理方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// 保留优先：依赖在保留的 target 配置下仍然声明，features 中对它的引用也保留
+    #[default]
+    KeepWins,
+    /// 删除优先：只要出现在被删除的 target 配置下，就从保留的 target 配置中一并删除，
+    /// 并清理 features 中的引用。顶层依赖表中声明的依赖不受影响
+    RemoveWins,
+}
+
+impl std::str::FromStr for ConflictResolution {
+    type Err = ProcessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep-wins" => Ok(ConflictResolution::KeepWins),
+            "remove-wins" => Ok(ConflictResolution::RemoveWins),
+            _ => Err(ProcessError::ConfigError(format!(
+                "未知的冲突处理方式: {}（可选 keep-wins、remove-wins）",
+                s
+            ))),
+        }
+    }
+}
+
+/// features 清理方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureMode {
+    /// 删除引用了被删除依赖的条目
+    #[default]
+    Apply,
+    /// 只在报告中记录将被删除的条目，不修改 features
+    ReportOnly,
+    /// 不处理 features
+    Skip,
+}
+
+impl std::str::FromStr for FeatureMode {
+    type Err = ProcessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "apply" => Ok(FeatureMode::Apply),
+            "report-only" => Ok(FeatureMode::ReportOnly),
+            "skip" => Ok(FeatureMode::Skip),
+            _ => Err(ProcessError::ConfigError(format!(
+                "未知的 features 清理方式: {}（可选 apply、report-only、skip）",
+                s
+            ))),
+        }
+    }
+}
+
+/// 被删除的 target 配置和 feature 的处理方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StripMode {
+    /// 从文档中删除
+    #[default]
+    Delete,
+    /// 删除后以注释的形式保留在原位置附近，便于审阅
+    Comment,
+}
+
+impl std::str::FromStr for StripMode {
+    type Err = ProcessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(StripMode::Delete),
+            "comment" => Ok(StripMode::Comment),
+            _ => Err(ProcessError::ConfigError(format!(
+                "未知的删除方式: {}（可选 delete、comment）",
+                s
+            ))),
+        }
+    }
+}
+
+/// 被删除的依赖声明，按依赖表类型分类
+///
+/// 记录的是从依赖表中删除的条目，同名依赖可能仍然声明在其他 target 配置或顶层依赖表中
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RemovedDeps {
+    /// `dependencies` 中删除的依赖
+    pub dependencies: BTreeSet<String>,
+    /// `dev-dependencies` 中删除的依赖
+    pub dev_dependencies: BTreeSet<String>,
+    /// `build-dependencies` 中删除的依赖
+    pub build_dependencies: BTreeSet<String>,
+}
+
+impl RemovedDeps {
+    /// 按依赖表名（`dependencies`、`dev-dependencies`、`build-dependencies`）记录，其他表名忽略
+    fn insert(&mut self, kind: &str, name: &str) {
+        let deps = match kind {
+            "dependencies" => &mut self.dependencies,
+            "dev-dependencies" => &mut self.dev_dependencies,
+            "build-dependencies" => &mut self.build_dependencies,
+            _ => return,
+        };
+        deps.insert(name.to_string());
+    }
+
+    /// 是否没有删除任何依赖
+    pub fn is_empty(&self) -> bool {
+        self.dependencies.is_empty()
+            && self.dev_dependencies.is_empty()
+            && self.build_dependencies.is_empty()
+    }
+
+    /// 三类依赖表中删除的依赖总数
+    pub fn len(&self) -> usize {
+        self.dependencies.len() + self.dev_dependencies.len() + self.build_dependencies.len()
+    }
+}
+
+/// 处理结果报告
+///
+/// 启用 `serde` feature 后可以序列化（例如输出为 JSON 供 CI 使用）
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProcessReport {
+    /// 输出是否与输入不同
+    pub changed: bool,
+    /// 被删除的 target 配置键，例如 `cfg(windows)`
+    pub removed_targets: Vec<String>,
+    /// 被删除的依赖声明，包括随 target 配置一起删除的依赖
+    pub removed_deps: RemovedDeps,
+    /// 处理前的 target 配置数（`[target]` 下的项数）
+    pub targets_before: usize,
+    /// 处理后的 target 配置数
+    pub targets_after: usize,
+    /// 处理前后依赖声明总数之差，包括 target 配置下的依赖和 dev-dependencies，
+    /// 移到顶层的依赖（见 [`Processor::hoist_deps`]）不计入
+    pub deps_removed_count: usize,
+    /// 被修改的 features：feature 名 -> 被删除的条目
+    ///
+    /// [`FeatureMode::ReportOnly`] 模式下记录的是将被删除的条目，文档本身不会被修改
+    pub modified_features: BTreeMap<String, Vec<String>>,
+    /// 因为条目被删除而变为空数组的 feature，按名称排序
+    ///
+    /// feature 本身默认保留，可以据此决定是否手动删除或调整（开启
+    /// [`Processor::remove_empty_features`] 时已被删除）；原本就是空数组的 feature 不在其中。
+    /// 与 [`Processor::prune_empty_features`] 无关，只是记录结果；
+    /// [`FeatureMode::ReportOnly`] 模式下记录的是将变为空的 feature
+    pub emptied_features: Vec<String>,
+    /// 处理后仍然存在的依赖名
+    ///
+    /// 包括 `[dependencies]`、`[build-dependencies]` 以及保留的 target 配置下的这两类依赖，
+    /// 不包括 dev-dependencies，即 Linux 构建实际需要的依赖集合
+    pub surviving_deps: BTreeSet<String>,
+    /// 被删除的依赖按平台分类：分类名（`windows`、`apple`、`android`、`wasm`、`other`）-> 依赖名
+    ///
+    /// 已知的平台特定依赖按已知列表分类，其余依赖按所在 target 配置中的平台关键字分类
+    pub removed_by_category: BTreeMap<String, Vec<String>>,
+    /// 激进模式下按名称推测删除的顶层依赖，见 [`Processor::aggressive`]
+    pub heuristic_removals: Vec<String>,
+    /// 从被删除的 target 配置移到顶层依赖表的依赖，见 [`Processor::hoist_deps`]
+    pub hoisted_deps: Vec<String>,
+    /// 出现在被删除的 target 配置中，但仍声明在顶层或保留的 target 配置中、因此没有删除的依赖
+    ///
+    /// 用于核对结果：删除的只是被删除配置中的那一处声明，features 中对它们的引用保留
+    pub ambiguous_deps: BTreeSet<String>,
+    /// 被修改的 `required-features`：target 条目（例如 `[[example]] win-console`）-> 被删除的条目
+    pub stripped_required_features: BTreeMap<String, Vec<String>>,
+    /// 因为 `required-features` 引用了已删除的 feature 或依赖而被删除的 target 条目，
+    /// 见 [`Processor::remove_unbuildable_targets`]
+    pub removed_cargo_targets: Vec<String>,
+    /// 指向已删除依赖而被删除的 `[patch]` 和 `[replace]` 条目，例如 `[patch.crates-io] windows-sys`，
+    /// 见 [`Processor::prune_patches`]
+    pub removed_patches: Vec<String>,
+    /// 只因依赖出现在已知平台依赖列表中（而不是出现在被删除的 target 配置下）
+    /// 被删除的 feature 条目，见 [`Processor::no_speculative`]
+    pub speculative_removals: BTreeSet<String>,
+    /// 实际执行的修改，按执行顺序排列，可以用 [`replay`] 重放
+    ///
+    /// [`FeatureMode::ReportOnly`] 模式下不包含 features 的修改
+    pub operations: Vec<Operation>,
+    /// 提示信息（不影响处理结果）
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -28,6 +545,21 @@ pub enum ProcessError {
     IoError(std::io::Error),
     ParseError(toml_edit::TomlError),
     CargoTomlError(cargo_toml::Error),
+    ConfigError(String),
+    /// 处理后没有剩余的依赖或 target 配置，参数为 crate 名
+    NoLinuxContent(String),
+    /// 输出文件需要修改但不可写（只读文件系统或权限不足），参数为路径和原始错误
+    NotWritable(String, std::io::Error),
+    /// 无法获取 rustc 的默认目标
+    RustcError(String),
+    /// 禁止推测删除时，有 feature 条目只因已知平台依赖列表被删除，参数为这些条目
+    SpeculativeRemoval(Vec<String>),
+    /// 删除比例超过上限，参数为统计对象（依赖或 target 配置）、删除数量、总数和上限
+    ExcessiveRemoval(String, usize, usize, f64),
+    /// 处理结果不再是有效的 Cargo 清单，参数为不一致之处的描述
+    ValidationError(String),
+    /// 输入不是有效的 UTF-8（TOML 要求 UTF-8 编码）
+    InvalidUtf8(std::str::Utf8Error),
 }
 
 impl std::fmt::Display for ProcessError {
@@ -36,12 +568,74 @@ impl std::fmt::Display for ProcessError {
             ProcessError::IoError(e) => write!(f, "IO错误: {}", e),
             ProcessError::ParseError(e) => write!(f, "TOML解析错误: {}", e),
             ProcessError::CargoTomlError(e) => write!(f, "Cargo.toml解析错误: {}", e),
+            ProcessError::ConfigError(msg) => write!(f, "配置错误: {}", msg),
+            ProcessError::NoLinuxContent(name) => write!(
+                f,
+                "{} 处理后没有剩余的依赖或 target 配置，可能不是面向 Linux 的 crate",
+                name
+            ),
+            ProcessError::NotWritable(path, e) => write!(
+                f,
+                "{} 需要修改但无法写入（只读文件系统或权限不足）: {}",
+                path, e
+            ),
+            ProcessError::RustcError(msg) => write!(f, "rustc 错误: {}", msg),
+            ProcessError::SpeculativeRemoval(items) => write!(
+                f,
+                "以下 feature 条目只因已知平台依赖列表被删除，请为其添加显式的 target 配置: {}",
+                items.join(", ")
+            ),
+            ProcessError::ExcessiveRemoval(what, removed, total, limit) => write!(
+                f,
+                "将删除 {} 个{}中的 {} 个，超过上限 {}%，可能处理了错误的文件",
+                total,
+                what,
+                removed,
+                limit * 100.0
+            ),
+            ProcessError::ValidationError(msg) => write!(f, "处理结果未通过校验: {}", msg),
+            ProcessError::InvalidUtf8(e) => write!(
+                f,
+                "输入不是有效的 UTF-8（第 {} 个字节起）: {}",
+                e.valid_up_to() + 1,
+                e
+            ),
         }
     }
 }
 
 impl std::error::Error for ProcessError {}
 
+impl ProcessError {
+    /// TOML 语法错误在解析内容中的字节范围，供编辑器集成标出出错的位置
+    ///
+    /// [`ProcessError::ParseError`] 和 `cargo_toml` 的 TOML 解析错误在有位置信息时返回，
+    /// [`ProcessError::InvalidUtf8`] 返回无效字节的范围，其他错误返回 None
+    pub fn span(&self) -> Option<std::ops::Range<usize>> {
+        match self {
+            ProcessError::ParseError(e) => e.span(),
+            ProcessError::CargoTomlError(cargo_toml::Error::Parse(e)) => e.span(),
+            ProcessError::InvalidUtf8(e) => {
+                let start = e.valid_up_to();
+                Some(start..start + e.error_len().unwrap_or(1))
+            }
+            _ => None,
+        }
+    }
+
+    /// TOML 语法错误在 `content` 中的位置，返回从 1 开始的 (行, 列)，列按字符计
+    ///
+    /// 位置来自 [`ProcessError::span`]，`content` 需要是出错时解析的内容
+    pub fn parse_location(&self, content: &str) -> Option<(usize, usize)> {
+        let offset = self.span()?.start.min(content.len());
+        let before = content.get(..offset)?;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+        Some((line, column))
+    }
+}
+
 impl From<std::io::Error> for ProcessError {
     fn from(err: std::io::Error) -> Self {
         ProcessError::IoError(err)
@@ -64,100 +658,1528 @@ impl From<cargo_toml::Error> for ProcessError {
 ///
 /// 使用 cargo_toml 解析 Cargo.toml 来智能识别平台特定依赖
 pub fn process_toml_file(input_path: &str, output_path: &str) -> Result<(), ProcessError> {
-    // 读取文件内容
-    let content = fs::read_to_string(input_path)?;
+    let content = read_manifest(input_path)?;
+    let output = process_toml_string(&content)?;
+    write_output(output_path, &output)
+}
 
-    // 使用 cargo_toml 从字节解析（避免工作区查找问题）
-    let manifest = Manifest::from_slice(content.as_bytes())?;
+/// 处理TOML文件，同时返回处理报告
+///
+/// 与 [`process_toml_file`] 使用相同的默认选项，与 [`process_toml_file_with_config`] 一样
+/// 以输入文件所在目录作为清单目录
+pub fn process_toml_file_reported(
+    input_path: &str,
+    output_path: &str,
+) -> Result<ProcessReport, ProcessError> {
+    let content = read_manifest(input_path)?;
+    let manifest_dir = Path::new(input_path).parent();
+    let (output, report) = ProcessOptions::default().run(&content, manifest_dir, &mut |_| {})?;
+    write_output(output_path, &output)?;
+    Ok(report)
+}
 
-    // 使用 toml_edit 编辑保留格式
-    let mut doc = content.parse::<DocumentMut>()?;
+/// 按 `config` 的选项处理TOML文件，同时返回处理报告
+///
+/// [`process_toml_file`] 相当于传入 [`StripConfig::default()`]
+pub fn process_toml_file_with_config(
+    input_path: &str,
+    output_path: &str,
+    config: &StripConfig,
+) -> Result<ProcessReport, ProcessError> {
+    let content = read_manifest(input_path)?;
+    let manifest_dir = Path::new(input_path).parent();
+    let (output, report) = config.0.run(&content, manifest_dir, &mut |_| {})?;
+    write_output(output_path, &output)?;
+    Ok(report)
+}
 
-    process_toml_doc(&mut doc, &manifest);
+/// 原子地写入输出文件，见 [`write_output_file`]
+pub(crate) fn write_output(output_path: &str, output: &str) -> Result<(), ProcessError> {
+    write_output_file(output_path, output, true)
+}
 
-    fs::write(output_path, doc.to_string())?;
-    Ok(())
+/// 写入输出文件
+///
+/// 输出文件已存在且内容相同时不写入，因此在只读文件系统（例如 Nix 构建沙箱）中
+/// 无需修改的处理也能成功；需要写入但目标不可写时返回 [`ProcessError::NotWritable`]。
+///
+/// `atomic` 为 true 时先写入同一目录下的临时文件，再重命名覆盖目标，进程中途被终止也不会
+/// 留下写了一半的文件；出错时删除临时文件。不支持重命名覆盖的文件系统可以传 false 直接写入
+pub fn write_output_file(
+    output_path: &str,
+    output: &str,
+    atomic: bool,
+) -> Result<(), ProcessError> {
+    if fs::read_to_string(output_path).is_ok_and(|existing| existing == output) {
+        return Ok(());
+    }
+    let result = if atomic {
+        write_atomic(Path::new(output_path), output)
+    } else {
+        fs::write(output_path, output)
+    };
+    result.map_err(|e| match e.kind() {
+        std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem => {
+            ProcessError::NotWritable(output_path.to_string(), e)
+        }
+        _ => ProcessError::IoError(e),
+    })
+}
+
+/// 写入临时文件 `.<文件名>.pre-tmp-<pid>` 后重命名覆盖目标
+///
+/// 目标是符号链接时写入链接指向的文件，已有文件的权限保留到新文件上
+fn write_atomic(path: &Path, output: &str) -> std::io::Result<()> {
+    let path = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path)?,
+        _ => path.to_path_buf(),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::other(format!("不是文件路径: {}", path.display())))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".pre-tmp-{}", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        file.write_all(output.as_bytes())?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+        fs::rename(&temp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
 }
 
 /// 处理TOML文档字符串（需要提供路径用于解析继承）
+///
+/// 先解析 `content`，其中的语法错误以 [`ProcessError::ParseError`] 返回，
+/// [`ProcessError::span`] 给出在 `content` 中的位置；之后才读取 `manifest_path`
 pub fn process_toml_string_with_path(
     content: &str,
     manifest_path: &str,
 ) -> Result<String, ProcessError> {
-    let manifest = Manifest::from_path(manifest_path)?;
     let mut doc = content.parse::<DocumentMut>()?;
+    let manifest = Manifest::from_path(manifest_path)?;
     process_toml_doc(&mut doc, &manifest);
-    Ok(doc.to_string())
+    Ok(restore_encoding(content, doc.to_string()))
 }
 
 /// 处理TOML文档字符串（不解析依赖，仅使用硬编码列表）
 pub fn process_toml_string(content: &str) -> Result<String, ProcessError> {
-    let mut doc = content.parse::<DocumentMut>()?;
-    // 创建一个 manifest 用于基本处理
-    let manifest = Manifest::from_slice(content.as_bytes())?;
-    process_toml_doc(&mut doc, &manifest);
-    Ok(doc.to_string())
+    process_str_with_filter(content, &TargetFilter::default())
+}
+
+/// 按指定的平台过滤条件处理字节形式的清单，返回处理后的字节
+///
+/// 调用方不需要先检查编码：无效的 UTF-8 以 [`ProcessError::InvalidUtf8`] 返回，
+/// [`ProcessError::span`] 给出无效字节的位置。开头的 BOM 和 CRLF 换行原样保留，
+/// 不需要修改的清单按字节原样返回
+pub fn process_toml_bytes(input: &[u8], filter: &TargetFilter) -> Result<Vec<u8>, ProcessError> {
+    process_str_with_filter(decode_manifest(input)?, filter).map(String::into_bytes)
+}
+
+/// 把读入的清单字节解码为文本，无效的 UTF-8 以带位置的 [`ProcessError::InvalidUtf8`] 报告
+///
+/// [`process_toml_bytes`]、[`process_toml_file`] 和命令行工具读取输入时都经过这里
+pub fn decode_manifest(input: &[u8]) -> Result<&str, ProcessError> {
+    std::str::from_utf8(input).map_err(ProcessError::InvalidUtf8)
+}
+
+fn process_str_with_filter(content: &str, filter: &TargetFilter) -> Result<String, ProcessError> {
+    let options = ProcessOptions {
+        filter: filter.clone(),
+        ..ProcessOptions::default()
+    };
+    let (output, _) = options.run(content, None, &mut |_| {})?;
+    Ok(output)
+}
+
+/// 读取清单文件，无效的 UTF-8 以带位置的 [`ProcessError::InvalidUtf8`] 报告
+fn read_manifest(path: &str) -> Result<String, ProcessError> {
+    decode_manifest(&fs::read(path)?).map(str::to_string)
+}
+
+/// 恢复 toml_edit 输出时丢失的格式：开头的 BOM 和 CRLF 换行
+///
+/// 没有修改时原样返回原内容；否则按原内容的第一个换行判断换行风格，
+/// 混用 LF 和 CRLF 的文件统一为第一个换行的风格
+fn restore_encoding(original: &str, output: String) -> String {
+    let body = original.strip_prefix('\u{feff}').unwrap_or(original);
+    if output == body.replace("\r\n", "\n") {
+        return original.to_string();
+    }
+    let crlf = original
+        .find('\n')
+        .is_some_and(|i| original[..i].ends_with('\r'));
+    let mut output = if crlf {
+        output.replace("\r\n", "\n").replace('\n', "\r\n")
+    } else {
+        output
+    };
+    if original.starts_with('\u{feff}') && !output.starts_with('\u{feff}') {
+        output.insert(0, '\u{feff}');
+    }
+    output
 }
 
 /// 处理TOML文档
 ///
 /// 核心处理函数，使用 cargo_toml 解析的 Manifest 来智能识别需要删除的配置
 pub fn process_toml_doc(doc: &mut DocumentMut, manifest: &Manifest) {
+    let mut report = ProcessReport::default();
+    process_doc_inner(
+        doc,
+        manifest,
+        &ProcessOptions::default(),
+        &mut |_| {},
+        &mut report,
+    );
+}
+
+/// 按 `config` 的选项处理TOML文档并返回处理报告
+///
+/// 与 [`Processor::process_doc`] 相同，只做删除和清理；[`process_toml_doc`] 相当于传入 [`StripConfig::default()`]
+pub fn process_toml_doc_with_config(
+    doc: &mut DocumentMut,
+    manifest: &Manifest,
+    config: &StripConfig,
+) -> ProcessReport {
+    config.0.process_doc(doc, manifest, &mut |_| {})
+}
+
+/// 处理TOML文档，按指定的平台过滤条件删除配置并返回处理报告
+///
+/// 报告的 [`ProcessReport::changed`] 需要调用方比较输入输出得到，这里始终为 false
+pub fn process_toml_doc_with_report(
+    doc: &mut DocumentMut,
+    manifest: &Manifest,
+    filter: &TargetFilter,
+) -> ProcessReport {
+    let options = ProcessOptions {
+        filter: filter.clone(),
+        ..ProcessOptions::default()
+    };
+    let mut report = ProcessReport::default();
+    process_doc_inner(doc, manifest, &options, &mut |_| {}, &mut report);
+    report
+}
+
+fn process_doc_inner(
+    doc: &mut DocumentMut,
+    manifest: &Manifest,
+    options: &ProcessOptions,
+    on_dep_removed: &mut dyn FnMut(&str),
+    report: &mut ProcessReport,
+) {
+    // 注释模式下保留处理前的文档，被删除的内容从中取出
+    let original = (options.strip_mode == StripMode::Comment).then(|| doc.clone());
+
+    // 处理前交叉检查 features（见 check_feature_consistency）：feature 名不一致时提示；
+    // 可选依赖产生的隐式 feature 在清理 features 之后，只对被删除的依赖提示
+    report
+        .warnings
+        .extend(feature_key_mismatches(doc, manifest));
+    let implicit_features = implicit_optional_features(doc, manifest);
+
     // 1. 使用 cargo_toml 解析并删除非Linux的target配置
-    let removed_deps = remove_non_linux_targets(doc, manifest);
+    // speculative 为只因出现在已知平台依赖列表中而加入的依赖名
+    let (mut removed_deps, speculative) =
+        remove_non_linux_targets(doc, manifest, options, on_dep_removed, report);
+
+    // 1.1 打包工具元数据中提到被删除的平台时提示（元数据原样保留）
+    let removed_targets: Vec<String> = report
+        .operations
+        .iter()
+        .filter_map(|op| match op {
+            Operation::RemoveTarget(key) => Some(key.clone()),
+            _ => None,
+        })
+        .collect();
+    report
+        .warnings
+        .extend(packaging::check_stale_packaging_metadata(
+            doc,
+            &removed_targets,
+            &options.filter,
+        ));
+
+    // 1.5 激进模式：按名称推测删除顶层的平台特定依赖。
+    // 名称片段与已知平台依赖列表一样是针对 Linux 整理的，保留其他平台时不使用
+    if options.aggressive && options.filter.keeps_linux() {
+        remove_heuristic_deps(
+            doc,
+            &options.keep_deps,
+            &mut removed_deps,
+            on_dep_removed,
+            report,
+        );
+    }
+
+    // 1.6 按名称删除指定的依赖
+    if !options.strip_deps.is_empty() {
+        strip_named_deps(
+            doc,
+            &options.strip_deps,
+            &mut removed_deps,
+            on_dep_removed,
+            report,
+        );
+    }
+
+    // 1.7 workspace 根清单：删除 [workspace.target] 和没有使用的 [workspace.dependencies] 平台依赖。
+    // 放在前面的删除之后，按名称删除的依赖不再算作在使用，处理一次的结果再处理也不会变化
+    remove_workspace_platform_deps(doc, options, &mut removed_deps, on_dep_removed, report);
+
+    // 1.8 删除指向已删除依赖的 [patch] 和 [replace] 条目
+    if options.prune_patches {
+        patches::prune_patches(doc, manifest, report);
+    }
 
     // 2. 清理features中对已删除依赖的引用
-    clean_features(doc, &removed_deps);
+    if options.feature_mode != FeatureMode::Skip {
+        clean_features(
+            doc,
+            &removed_deps,
+            &speculative,
+            options.feature_mode,
+            options.prune_empty_features || options.remove_empty_features,
+            options.remove_empty_features,
+            report,
+        );
+    }
+
+    // 2.1 被删除的可选依赖产生的隐式 feature 随依赖一起消失，依赖方通过这个名称启用它时会出错
+    for name in implicit_features
+        .iter()
+        .filter(|name| removed_deps.contains(*name))
+    {
+        report.warnings.push(format!(
+            "可选依赖 `{}` 已删除，它产生的隐式 feature `{}` 不再存在",
+            name, name
+        ));
+    }
+
+    // 2.2 清理 [[bin]]、[[example]] 等的 required-features 中对已删除的 feature 和依赖的引用
+    if options.feature_mode == FeatureMode::Apply {
+        clean_required_features(
+            doc,
+            &removed_deps,
+            options.remove_unbuildable_targets,
+            report,
+        );
+    }
+
+    // 3. 注释模式：把整个删除的 target 配置和 feature 写回为注释
+    if let Some(original) = &original {
+        comment::comment_out_removed(doc, original, &report.operations);
+    }
+
+    for operation in &report.operations {
+        match operation {
+            Operation::RemoveTarget(key) => report.removed_targets.push(key.clone()),
+            Operation::RemoveDep { kind, name, .. } => report.removed_deps.insert(kind, name),
+            Operation::RemoveWorkspaceDep(name) => report.removed_deps.insert("dependencies", name),
+            _ => {}
+        }
+    }
+    report.surviving_deps = collect_surviving_deps(doc);
+}
+
+/// 从所有依赖表（顶层和 target 配置下的 dependencies、dev-dependencies、build-dependencies）
+/// 中删除指定名称的依赖，与平台判断无关
+///
+/// 删除后变为空的依赖表和 target 配置一并删除
+fn strip_named_deps(
+    doc: &mut DocumentMut,
+    names: &BTreeSet<String>,
+    removed_deps: &mut HashSet<String>,
+    on_dep_removed: &mut dyn FnMut(&str),
+    report: &mut ProcessReport,
+) {
+    const DEP_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+    // features 不能引用 dev-dependencies，只有从另外两类依赖表中删除的依赖用于清理 features
+    let mut stripped = BTreeSet::new();
+    let mut referable = BTreeSet::new();
+    let mut strip = |table: &mut dyn toml_edit::TableLike, target: Option<&str>| {
+        for kind in DEP_TABLES {
+            if let Some(deps) = table.get_mut(kind).and_then(|t| t.as_table_like_mut()) {
+                for name in names {
+                    if deps.remove(name).is_some() {
+                        log_debug!("删除指定的依赖 {}（{}）", name, kind);
+                        stripped.insert(name.clone());
+                        if kind != "dev-dependencies" {
+                            referable.insert(name.clone());
+                        }
+                        report.operations.push(Operation::RemoveDep {
+                            target: target.map(str::to_string),
+                            kind: kind.to_string(),
+                            name: name.clone(),
+                        });
+                    }
+                }
+                if deps.is_empty() {
+                    table.remove(kind);
+                }
+            }
+        }
+    };
+
+    strip(doc.as_table_mut(), None);
+    if let Some(targets) = doc.get_mut("target").and_then(|t| t.as_table_like_mut()) {
+        let mut emptied = Vec::new();
+        for (spec, target) in targets.iter_mut() {
+            if let Some(target) = target.as_table_like_mut() {
+                strip(target, Some(spec.get()));
+                if target.is_empty() {
+                    emptied.push(spec.get().to_string());
+                }
+            }
+        }
+        for spec in emptied {
+            targets.remove(&spec);
+        }
+        if targets.is_empty() {
+            doc.remove("target");
+        }
+    }
+
+    for dep in stripped {
+        on_dep_removed(&dep);
+    }
+    removed_deps.extend(referable);
+}
+
+/// 依赖名中出现这些片段（按 `-`/`_` 切分）时，视为平台特定依赖
+const PLATFORM_NAME_TOKENS: &[&str] = &[
+    "windows",
+    "win32",
+    "winapi",
+    "wincon",
+    "winreg",
+    "uwp",
+    "objc",
+    "objc2",
+    "cocoa",
+    "macos",
+    "ios",
+    "darwin",
+    "apple",
+    "android",
+    "ndk",
+    "wasm",
+    "emscripten",
+];
+
+/// 按名称判断依赖是否看起来是平台特定的，例如 `my-windows-helper`、`objc2-vendored`
+pub fn looks_platform_specific(dep: &str) -> bool {
+    dep.split(['-', '_'])
+        .any(|token| PLATFORM_NAME_TOKENS.contains(&token.to_ascii_lowercase().as_str()))
+}
+
+/// 删除顶层 `[dependencies]`/`[build-dependencies]` 中名称看起来平台特定的依赖
+///
+/// 这些依赖没有 target 条件作为依据，只凭名称推测，每一个都记录到
+/// [`ProcessReport::heuristic_removals`]，并作为提示信息输出
+fn remove_heuristic_deps(
+    doc: &mut DocumentMut,
+    keep_deps: &BTreeSet<String>,
+    removed_deps: &mut HashSet<String>,
+    on_dep_removed: &mut dyn FnMut(&str),
+    report: &mut ProcessReport,
+) {
+    let mut removed = BTreeSet::new();
+    for kind in ["dependencies", "build-dependencies"] {
+        if let Some(table) = doc.get_mut(kind).and_then(|t| t.as_table_like_mut()) {
+            let names: Vec<String> = table
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .filter(|name| looks_platform_specific(name) && !keep_deps.contains(name))
+                .collect();
+            for name in names {
+                table.remove(&name);
+                log_debug!("激进模式：删除名称像平台特定依赖的 {}（{}）", name, kind);
+                report.operations.push(Operation::RemoveDep {
+                    target: None,
+                    kind: kind.to_string(),
+                    name: name.clone(),
+                });
+                removed.insert(name);
+            }
+            if table.is_empty() {
+                doc.remove(kind);
+            }
+        }
+    }
+
+    // 仍被保留的 target 配置使用的依赖不算删除
+    let surviving = collect_surviving_deps(doc);
+    removed.retain(|dep| !surviving.contains(dep));
+    for dep in removed {
+        on_dep_removed(&dep);
+        report
+            .warnings
+            .push(format!("按名称推测删除了平台特定依赖: {}", dep));
+        removed_deps.insert(dep.clone());
+        report.heuristic_removals.push(dep);
+    }
+}
+
+/// 收集文档中剩余的依赖名（不含 dev-dependencies）
+fn collect_surviving_deps(doc: &DocumentMut) -> BTreeSet<String> {
+    const DEP_TABLES: [&str; 2] = ["dependencies", "build-dependencies"];
+
+    let mut deps = BTreeSet::new();
+    let mut collect = |table: &dyn toml_edit::TableLike| {
+        for kind in DEP_TABLES {
+            if let Some(dep_table) = table.get(kind).and_then(|t| t.as_table_like()) {
+                deps.extend(dep_table.iter().map(|(name, _)| name.to_string()));
+            }
+        }
+    };
+
+    collect(doc.as_table());
+    if let Some(targets) = doc.get("target").and_then(|t| t.as_table_like()) {
+        for (_, target) in targets.iter() {
+            if let Some(target) = target.as_table_like() {
+                collect(target);
+            }
+        }
+    }
+
+    deps
+}
+
+/// 依赖删除回调，参数为依赖名
+pub type DepRemovedCallback = Box<dyn FnMut(&str)>;
+
+/// 处理选项（不含回调），可以在线程间共享
+#[derive(Debug, Default, Clone)]
+struct ProcessOptions {
+    filter: TargetFilter,
+    feature_mode: FeatureMode,
+    aggressive: bool,
+    strip_deps: BTreeSet<String>,
+    /// 不删除的依赖，见 [`Processor::keep_dep`]
+    keep_deps: BTreeSet<String>,
+    prune_empty_features: bool,
+    remove_empty_features: bool,
+    #[cfg(feature = "path-deps")]
+    resolve_path_deps: bool,
+    no_speculative: bool,
+    header: bool,
+    require_content: bool,
+    max_removal_ratio: Option<f64>,
+    known_deps: KnownDeps,
+    conflict_resolution: ConflictResolution,
+    /// 处理后重新解析输出并检查 features 引用，见 [`Processor::validate`]
+    validate: bool,
+    /// 被删除的 target 配置中有 dev-dependencies 时保留它们，见 [`Processor::strip_dev_dependencies`]
+    keep_dev_deps: bool,
+    /// 把只声明在被删除的 target 配置中的非平台依赖移到顶层依赖表，见 [`Processor::hoist_deps`]
+    hoist_deps: bool,
+    /// required-features 引用了已删除的 feature 或依赖时删除整个 target 条目，
+    /// 见 [`Processor::remove_unbuildable_targets`]
+    remove_unbuildable_targets: bool,
+    /// 删除指向已删除依赖的 `[patch]` 和 `[replace]` 条目，见 [`Processor::prune_patches`]
+    prune_patches: bool,
+    /// 被删除的 target 配置和 feature 的处理方式，见 [`Processor::strip_mode`]
+    strip_mode: StripMode,
+    /// 处理 workspace 根清单时，各成员处理后仍在使用的依赖名，见 [`process_workspace`]
+    workspace_member_deps: Option<BTreeSet<String>>,
+}
+
+impl ProcessOptions {
+    fn process_doc(
+        &self,
+        doc: &mut DocumentMut,
+        manifest: &Manifest,
+        on_dep_removed: &mut dyn FnMut(&str),
+    ) -> ProcessReport {
+        let mut report = ProcessReport::default();
+        let (deps_before, targets_before) = count_deps_and_targets(doc);
+        process_doc_inner(doc, manifest, self, on_dep_removed, &mut report);
+        let (deps_after, targets_after) = count_deps_and_targets(doc);
+        report.targets_before = targets_before;
+        report.targets_after = targets_after;
+        report.deps_removed_count = deps_before.saturating_sub(deps_after);
+        report
+    }
+
+    /// `manifest_dir` 为清单所在目录，用于定位 path 依赖；未知时为 None
+    fn run(
+        &self,
+        content: &str,
+        manifest_dir: Option<&std::path::Path>,
+        on_dep_removed: &mut dyn FnMut(&str),
+    ) -> Result<(String, ProcessReport), ProcessError> {
+        let mut doc = content.parse::<DocumentMut>()?;
+        let (manifest, skipped) = parse_manifest(&doc, content)?;
+        let mut report = self.process_checked(&mut doc, &manifest, manifest_dir, on_dep_removed)?;
+        report.warnings.splice(0..0, skipped);
+        let mut output = doc.to_string();
+        if self.header {
+            output = apply_generated_header(&output, &self.filter);
+        }
+        let output = restore_encoding(content, output);
+        report.changed = output != content;
+        if self.validate && report.changed {
+            validate_output(content, &output)?;
+            self.check_idempotent(&output, manifest_dir)?;
+        }
+        Ok((output, report))
+    }
+
+    /// 用同样的选项再处理一次输出，结果必须不变：处理过的清单再处理是空操作
+    fn check_idempotent(
+        &self,
+        output: &str,
+        manifest_dir: Option<&std::path::Path>,
+    ) -> Result<(), ProcessError> {
+        let options = ProcessOptions {
+            validate: false,
+            ..self.clone()
+        };
+        let (again, _) = options.run(output, manifest_dir, &mut |_| {})?;
+        if again == output {
+            Ok(())
+        } else {
+            Err(ProcessError::ValidationError(
+                "再次处理输出时结果发生变化".to_string(),
+            ))
+        }
+    }
+
+    /// 处理已解析的文档，并做 `require_content`、`max_removal_ratio` 和 `no_speculative` 的检查
+    fn process_checked(
+        &self,
+        doc: &mut DocumentMut,
+        manifest: &Manifest,
+        manifest_dir: Option<&std::path::Path>,
+        on_dep_removed: &mut dyn FnMut(&str),
+    ) -> Result<ProcessReport, ProcessError> {
+        let had_content = has_deps_or_targets(doc);
+        let before = count_deps_and_targets(doc);
+        #[cfg(feature = "path-deps")]
+        let mut report = self.process_doc(doc, manifest, on_dep_removed);
+        #[cfg(not(feature = "path-deps"))]
+        let report = self.process_doc(doc, manifest, on_dep_removed);
+        #[cfg(feature = "path-deps")]
+        if let (true, Some(dir)) = (self.resolve_path_deps, manifest_dir) {
+            path_deps::strip_platform_dep_features(doc, dir, self, &mut report);
+        }
+        #[cfg(not(feature = "path-deps"))]
+        let _ = manifest_dir;
+        if self.require_content && had_content && !has_deps_or_targets(doc) {
+            let name = manifest
+                .package
+                .as_ref()
+                .map_or_else(|| "<unknown>".to_string(), |p| p.name.clone());
+            return Err(ProcessError::NoLinuxContent(name));
+        }
+        if let Some(limit) = self.max_removal_ratio {
+            check_removal_ratio(before, count_deps_and_targets(doc), limit)?;
+        }
+        if self.no_speculative && !report.speculative_removals.is_empty() {
+            return Err(ProcessError::SpeculativeRemoval(
+                report.speculative_removals.into_iter().collect(),
+            ));
+        }
+        Ok(report)
+    }
+}
+
+/// 处理选项，供 [`process_toml_doc_with_config`] 和 [`process_toml_file_with_config`] 使用
+///
+/// 默认值与 [`process_toml_file`] 的行为（保留 Linux）相同。选项通过 [`StripConfig::builder`]
+/// 返回的 [`Processor`] 设置，最后调用 [`Processor::build`]；不含回调，可以在线程间共享
+#[derive(Debug, Default, Clone)]
+pub struct StripConfig(ProcessOptions);
+
+impl StripConfig {
+    /// 选项的构建器，例如 `StripConfig::builder().aggressive(true).validate(true).build()`
+    pub fn builder() -> Processor {
+        Processor::new()
+    }
+}
+
+impl From<StripConfig> for Processor {
+    fn from(config: StripConfig) -> Self {
+        Processor {
+            options: config.0,
+            ..Processor::default()
+        }
+    }
+}
+
+/// 可复用的处理器
+///
+/// 在默认处理流程之外，允许挂载回调（例如删除依赖时同步清理 vendor 目录）
+#[derive(Default)]
+pub struct Processor {
+    options: ProcessOptions,
+    on_dep_removed: Option<DepRemovedCallback>,
+    /// 直接覆盖输出文件，不经过临时文件，见 [`Processor::atomic_write`]
+    direct_write: bool,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置平台过滤条件
+    pub fn filter(mut self, filter: TargetFilter) -> Self {
+        self.options.filter = filter;
+        self
+    }
+
+    /// 当前的平台过滤条件，例如用于以同样的条件处理 `.cargo/config.toml`
+    pub fn target_filter(&self) -> &TargetFilter {
+        &self.options.filter
+    }
+
+    /// 设置依赖同时出现在保留和删除的 target 配置下时的处理方式，默认保留优先
+    pub fn conflict_resolution(mut self, resolution: ConflictResolution) -> Self {
+        self.options.conflict_resolution = resolution;
+        self
+    }
+
+    /// 设置 features 清理方式
+    pub fn feature_mode(mut self, mode: FeatureMode) -> Self {
+        self.options.feature_mode = mode;
+        self
+    }
+
+    /// 激进模式：按名称推测删除顶层的平台特定依赖（默认关闭）
+    ///
+    /// 用于平台依赖名称不在已知列表中的 vendored crate。名称推测可能误删，
+    /// 被删除的依赖记录在 [`ProcessReport::heuristic_removals`] 中。只在保留 Linux 时生效
+    pub fn aggressive(mut self, aggressive: bool) -> Self {
+        self.options.aggressive = aggressive;
+        self
+    }
+
+    /// 从所有依赖表中删除指定名称的依赖，并清理 features 中对它的引用（与平台判断无关）
+    ///
+    /// 可以多次调用，每次追加一个依赖名
+    pub fn strip_dep(mut self, name: impl Into<String>) -> Self {
+        self.options.strip_deps.insert(name.into());
+        self
+    }
+
+    /// 不删除指定名称的依赖，用于已知平台依赖列表或 `aggressive` 误判的跨平台依赖
+    ///
+    /// 顶层依赖表中的声明和 features 中对它的引用都保留；只声明在被删除的 target 配置中时移到顶层依赖表
+    /// （同 [`Processor::hoist_deps`]）。依赖名为清单中的本地名，`strip_dep` 指定的依赖仍然删除。
+    /// 可以多次调用，每次追加一个依赖名
+    pub fn keep_dep(mut self, name: impl Into<String>) -> Self {
+        self.options.keep_deps.insert(name.into());
+        self
+    }
+
+    /// 是否删除对清理后变为空数组的 feature 的引用（默认关闭）
+    ///
+    /// 例如 `win = ["dep:windows-sys"]` 被清空后，把 `win` 从 `default = ["win"]` 中删除，
+    /// 并继续处理因此变空的 feature
+    pub fn prune_empty_features(mut self, prune: bool) -> Self {
+        self.options.prune_empty_features = prune;
+        self
+    }
+
+    /// 是否删除清理后变为空数组的 feature 本身（默认关闭）
+    ///
+    /// 同时开启 [`Processor::prune_empty_features`]，对这些 feature 的引用先被删除，
+    /// 删除后不会留下指向不存在的 feature 的条目。原本就是空数组的 feature 保留
+    pub fn remove_empty_features(mut self, remove: bool) -> Self {
+        self.options.remove_empty_features = remove;
+        self
+    }
+
+    /// 是否随被删除的 target 配置一起删除其中的 dev-dependencies（默认开启）
+    ///
+    /// 关闭时，被删除的 target 配置中有 dev-dependencies 的，只删除其中的 dependencies 和
+    /// build-dependencies，target 配置和 dev-dependencies 保留，测试仍可以在各平台的 CI 上运行
+    pub fn strip_dev_dependencies(mut self, strip: bool) -> Self {
+        self.options.keep_dev_deps = !strip;
+        self
+    }
+
+    /// 删除 target 配置时，是否把其中不在已知平台依赖列表中、也没有在别处声明的依赖移到顶层（默认关闭）
+    ///
+    /// 用于 `[target.'cfg(windows)'.dependencies] tokio = { ... }` 这类只是为跨平台依赖附加 features
+    /// 的写法：顶层漏写了 tokio 时，默认会随 target 配置一起删除。移动的依赖记录在
+    /// [`ProcessReport::hoisted_deps`] 中，features 中对它们的引用保留
+    pub fn hoist_deps(mut self, hoist: bool) -> Self {
+        self.options.hoist_deps = hoist;
+        self
+    }
+
+    /// 被删除的 target 配置和 feature 是直接删除还是改为注释（默认直接删除）
+    ///
+    /// [`StripMode::Comment`] 时，整个删除的 target 配置和 `--remove-empty-features` 删除的 feature
+    /// 按原格式写回为 `#` 开头的注释行，放在原位置之后的第一个表头或 feature 之前，输出仍是有效的 TOML。
+    /// 只删除了部分内容的 target 配置、feature 中被删除的条目和 `[workspace]` 下的内容不写回；
+    /// 报告中的操作不包括这些注释，[`replay`] 重放时照常删除
+    pub fn strip_mode(mut self, mode: StripMode) -> Self {
+        self.options.strip_mode = mode;
+        self
+    }
+
+    /// `[[bin]]`、`[[example]]`、`[[test]]`、`[[bench]]` 的 `required-features` 引用了已删除的
+    /// feature 或依赖时，是否删除整个条目（默认关闭）
+    ///
+    /// 默认只从 `required-features` 中删除这些条目，例如只在 Windows 上构建的示例
+    /// `required-features = ["wincon"]` 在 wincon 被删除后变为无条件构建；开启后删除整个
+    /// `[[example]]`。删除的条目记录在 [`ProcessReport::removed_cargo_targets`] 中
+    pub fn remove_unbuildable_targets(mut self, remove: bool) -> Self {
+        self.options.remove_unbuildable_targets = remove;
+        self
+    }
+
+    /// 是否删除 `[patch.<source>]` 和 `[replace]` 中指向已删除依赖的条目（默认关闭）
+    ///
+    /// 只删除处理前在清单中声明、处理后不再声明的包的条目，避免 Cargo 提示未使用的 patch；
+    /// 间接依赖仍可能使用这些 patch，因此默认保留。删除的条目记录在 [`ProcessReport::removed_patches`] 中
+    pub fn prune_patches(mut self, prune: bool) -> Self {
+        self.options.prune_patches = prune;
+        self
+    }
+
+    /// 读取 path 依赖自身的清单，删除依赖方启用的、只在非 Linux 平台生效的 feature（默认关闭）
+    ///
+    /// 需要清单所在目录来定位 path 依赖，因此只在 [`Processor::process_file`] 中生效。
+    /// 非 path 依赖（crates.io、git）的清单无法获取，不做处理
+    #[cfg(feature = "path-deps")]
+    pub fn resolve_path_deps(mut self, resolve: bool) -> Self {
+        self.options.resolve_path_deps = resolve;
+        self
+    }
+
+    /// 禁止推测删除：有 feature 条目只因已知平台依赖列表被删除时报错（默认关闭）
+    ///
+    /// 用于只接受有依据的删除，出错时需要为相关依赖添加显式的 target 配置，
+    /// 见 [`ProcessReport::speculative_removals`]
+    pub fn no_speculative(mut self, no_speculative: bool) -> Self {
+        self.options.no_speculative = no_speculative;
+        self
+    }
+
+    /// 是否在输出开头添加生成标记注释，见 [`apply_generated_header`]
+    pub fn header(mut self, header: bool) -> Self {
+        self.options.header = header;
+        self
+    }
+
+    /// 处理后是否校验输出仍是有效的 Cargo 清单（默认关闭）
+    ///
+    /// 用 `cargo_toml` 重新解析输出，并检查 features 中的 `dep:x`、`x/feature` 和纯名称引用
+    /// 都指向仍然声明的依赖或 feature；输入中原本就有的问题不计。
+    /// 同时用同样的选项再处理一次输出，确认结果不再变化。
+    /// 有问题时返回 [`ProcessError::ValidationError`]，不写入输出
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.options.validate = validate;
+        self
+    }
+
+    /// 处理后没有剩余依赖和 target 配置时是否报错
+    ///
+    /// 只在输入本身有依赖或 target 配置时检查，没有任何依赖的 crate 不受影响
+    pub fn require_content(mut self, require: bool) -> Self {
+        self.options.require_content = require;
+        self
+    }
+
+    /// 删除比例上限（0 到 1）：删除的依赖或 target 配置超过总数的这一比例时报错
+    ///
+    /// 用于防止误处理平台专属 crate 或错误的文件，默认不检查
+    pub fn max_removal_ratio(mut self, ratio: f64) -> Self {
+        self.options.max_removal_ratio = Some(ratio);
+        self
+    }
+
+    /// 使用叠加了外部文件修改的已知平台依赖列表，见 [`KnownDeps`]
+    pub fn known_deps(mut self, known_deps: KnownDeps) -> Self {
+        self.options.known_deps = known_deps;
+        self
+    }
+
+    /// 在已知平台依赖列表中添加依赖名，例如内置列表中还没有的新平台 crate
+    ///
+    /// 在 [`Processor::known_deps`] 之后调用时添加到其列表上
+    pub fn extra_platform_deps(mut self, deps: &HashSet<String>) -> Self {
+        self.options.known_deps.extend(deps);
+        self
+    }
+
+    /// [`Processor::process_file`] 是否原子地写入输出文件（默认 true），见 [`write_output_file`]
+    pub fn atomic_write(mut self, atomic: bool) -> Self {
+        self.direct_write = !atomic;
+        self
+    }
+
+    /// 设置依赖删除回调
+    ///
+    /// 对每个最终被删除的依赖调用一次（已排除仍被保留 target 或普通依赖使用的依赖），
+    /// 只报告清单中真实声明过的依赖，不包括仅用于清理 features 的已知平台依赖列表
+    pub fn on_dep_removed(mut self, callback: impl FnMut(&str) + 'static) -> Self {
+        self.on_dep_removed = Some(Box::new(callback));
+        self
+    }
+
+    /// 取出处理选项，见 [`StripConfig::builder`]
+    ///
+    /// 依赖删除回调和 [`Processor::atomic_write`] 不属于处理选项，不会带入结果
+    pub fn build(self) -> StripConfig {
+        StripConfig(self.options)
+    }
+
+    /// 处理选项的指纹，选项不同时指纹也不同，用作 [`Cache`] 的失效依据
+    pub fn fingerprint(&self) -> String {
+        format!("{:?}", self.options)
+    }
+
+    /// 转换为可重复调用的闭包
+    ///
+    /// 闭包只捕获处理选项，每次调用除解析外没有额外的准备工作，并且可以跨线程共享
+    /// （例如用于 rayon 的 `par_iter().map(...)`）。依赖删除回调不会带入闭包，需要回调时使用 [`Processor::run`]
+    pub fn into_fn(
+        self,
+    ) -> impl Fn(&str) -> Result<(String, ProcessReport), ProcessError> + Send + Sync {
+        let options = self.options;
+        move |content| options.run(content, None, &mut |_| {})
+    }
+
+    /// 处理TOML文档
+    ///
+    /// 只做删除和清理，不做 [`Processor::require_content`] 等检查，见 [`Processor::process_document`]
+    pub fn process_doc(&mut self, doc: &mut DocumentMut, manifest: &Manifest) -> ProcessReport {
+        let mut noop = |_: &str| {};
+        let on_dep_removed: &mut dyn FnMut(&str) = match self.on_dep_removed.as_mut() {
+            Some(callback) => callback.as_mut(),
+            None => &mut noop,
+        };
+        self.options.process_doc(doc, manifest, on_dep_removed)
+    }
+
+    /// 处理已解析的文档和清单，适合已经为其他分析解析过清单的调用方，避免重复解析
+    ///
+    /// 与 [`Processor::run`] 做相同的检查（[`Processor::require_content`]、
+    /// [`Processor::max_removal_ratio`]、[`Processor::no_speculative`]），检查失败时文档可能已被修改。
+    /// 生成标记和输出校验作用于文本，这里不做；也不解析 path 依赖（需要清单路径）。
+    /// 报告的 [`ProcessReport::changed`] 始终为 false
+    pub fn process_document(
+        &mut self,
+        doc: &mut DocumentMut,
+        manifest: &Manifest,
+    ) -> Result<ProcessReport, ProcessError> {
+        let mut noop = |_: &str| {};
+        let on_dep_removed: &mut dyn FnMut(&str) = match self.on_dep_removed.as_mut() {
+            Some(callback) => callback.as_mut(),
+            None => &mut noop,
+        };
+        self.options
+            .process_checked(doc, manifest, None, on_dep_removed)
+    }
+
+    /// 处理TOML文档字符串，同时返回处理报告
+    pub fn run(&mut self, content: &str) -> Result<(String, ProcessReport), ProcessError> {
+        let mut noop = |_: &str| {};
+        let on_dep_removed: &mut dyn FnMut(&str) = match self.on_dep_removed.as_mut() {
+            Some(callback) => callback.as_mut(),
+            None => &mut noop,
+        };
+        self.options.run(content, None, on_dep_removed)
+    }
+
+    /// 处理TOML文档字符串
+    pub fn process_str(&mut self, content: &str) -> Result<String, ProcessError> {
+        self.run(content).map(|(output, _)| output)
+    }
+
+    /// 处理TOML文件
+    pub fn process_file(
+        &mut self,
+        input_path: &str,
+        output_path: &str,
+    ) -> Result<ProcessReport, ProcessError> {
+        let content = read_manifest(input_path)?;
+        let (output, report) = self.run_with_path(&content, input_path)?;
+        write_output_file(output_path, &output, !self.direct_write)?;
+        Ok(report)
+    }
+
+    /// 处理从 `manifest_path` 读取的内容，不写入文件
+    ///
+    /// 与 [`Processor::run`] 不同，清单路径用于定位 path 依赖（见 `resolve_path_deps`）
+    pub fn run_with_path(
+        &mut self,
+        content: &str,
+        manifest_path: &str,
+    ) -> Result<(String, ProcessReport), ProcessError> {
+        let mut noop = |_: &str| {};
+        let on_dep_removed: &mut dyn FnMut(&str) = match self.on_dep_removed.as_mut() {
+            Some(callback) => callback.as_mut(),
+            None => &mut noop,
+        };
+        let manifest_dir = std::path::Path::new(manifest_path).parent();
+        self.options.run(content, manifest_dir, on_dep_removed)
+    }
+}
+
+/// 内容中是否有指定的顶层表，例如 `target`（包括 `[target.'cfg(...)'.dependencies]` 形式）
+///
+/// 先逐行扫描原始文本，用于批量处理时快速跳过无关的文件：没有以该表开头的表头
+/// （`[table]`、`[table.`、`[[table]]`），也没有以 `table =` 或 `table.` 开头的键时
+/// 直接返回 false，不解析。找到表头且内容中没有多行字符串时直接返回 true；其余情况
+/// （只找到键，可能属于其他表；或者匹配的行可能在多行字符串中）再解析确认
+pub fn has_top_level_table(content: &str, table: &str) -> bool {
+    if !content.contains(table) {
+        return false;
+    }
+    let mut header_found = false;
+    let mut key_found = false;
+    for line in content.lines() {
+        let line = line.trim_start();
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.strip_prefix('[').unwrap_or(header);
+            header_found |= leading_key_is(header, table, &['.', ']']);
+        } else {
+            // 键可能属于前面的某个表（例如 `[build] target = ...`），需要解析确认
+            key_found |= leading_key_is(line, table, &['.', '=']);
+        }
+    }
+    if !header_found && !key_found {
+        return false;
+    }
+    let has_multiline_string = content.contains("\"\"\"") || content.contains("'''");
+    if header_found && !has_multiline_string {
+        return true;
+    }
+    content
+        .parse::<DocumentMut>()
+        .is_ok_and(|doc| doc.contains_key(table))
+}
+/// 去掉开头空白后，`text` 的第一段键名（裸键或带引号的键）是否为 `key`，
+/// 且其后（跳过空白）紧跟 `terminators` 中的某个字符
+fn leading_key_is(text: &str, key: &str, terminators: &[char]) -> bool {
+    let text = text.trim_start();
+    let rest = match text.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let inner = &text[1..];
+            match inner.find(quote) {
+                Some(end) if &inner[..end] == key => &inner[end + 1..],
+                _ => return false,
+            }
+        }
+        _ => match text.strip_prefix(key) {
+            Some(rest)
+                if !rest
+                    .starts_with(|c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+            {
+                rest
+            }
+            _ => return false,
+        },
+    };
+    rest.trim_start().starts_with(terminators)
+}
+
+/// `[package] build = "..."` 指定的自定义构建脚本路径
+fn custom_build_script(doc: &DocumentMut) -> Option<String> {
+    doc.get("package")
+        .and_then(|p| p.as_table_like())
+        .and_then(|p| p.get("build"))
+        .and_then(|b| b.as_str())
+        .map(str::to_string)
+}
+
+/// 文档中是否还有依赖或 target 配置
+fn has_deps_or_targets(doc: &DocumentMut) -> bool {
+    [
+        "dependencies",
+        "dev-dependencies",
+        "build-dependencies",
+        "target",
+    ]
+    .iter()
+    .any(|key| {
+        doc.get(key)
+            .and_then(|item| item.as_table_like())
+            .is_some_and(|table| !table.is_empty())
+    })
+}
+
+/// 统计依赖数量（包括 target 配置下的依赖）和 target 配置数量
+fn count_deps_and_targets(doc: &DocumentMut) -> (usize, usize) {
+    const DEP_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+    let count_deps = |table: &dyn toml_edit::TableLike| {
+        DEP_TABLES
+            .iter()
+            .filter_map(|key| table.get(key).and_then(|item| item.as_table_like()))
+            .map(|deps| deps.len())
+            .sum::<usize>()
+    };
+
+    let mut deps = count_deps(doc.as_table());
+    let mut targets = 0;
+    if let Some(target_table) = doc.get("target").and_then(|t| t.as_table_like()) {
+        for (_, config) in target_table.iter() {
+            targets += 1;
+            if let Some(config) = config.as_table_like() {
+                deps += count_deps(config);
+            }
+        }
+    }
+    (deps, targets)
+}
+
+/// 解析 `cargo_toml` 的 [`Manifest`]，同时返回格式不正确、原样保留的部分的警告
+///
+/// `features` 或 `target` 不是表、某个 feature 不是字符串数组、某个 target 配置或其中的依赖表
+/// 不是表时 `cargo_toml` 无法解析整个清单。这时在文档的副本中去掉这些部分后重新解析，
+/// 处理时它们不会被修改（见 [`is_well_formed_target_config`]）；去掉后仍无法解析时返回原来的错误
+fn parse_manifest(
+    doc: &DocumentMut,
+    content: &str,
+) -> Result<(Manifest, Vec<String>), ProcessError> {
+    let error = match Manifest::from_slice(content.as_bytes()) {
+        Ok(manifest) => return Ok((manifest, Vec::new())),
+        Err(e) => e,
+    };
+
+    let mut sanitized = doc.clone();
+    let mut skipped = Vec::new();
+    for section in ["features", "target"] {
+        let Some(item) = sanitized.get_mut(section) else {
+            continue;
+        };
+        let Some(table) = item.as_table_like_mut() else {
+            sanitized.remove(section);
+            skipped.push(format!("[{}] 不是表，已原样保留", section));
+            continue;
+        };
+        let malformed: Vec<String> = table
+            .iter()
+            .filter(|(_, value)| match section {
+                "features" => !value
+                    .as_array()
+                    .is_some_and(|array| array.iter().all(|v| v.as_str().is_some())),
+                _ => !is_well_formed_target_config(value),
+            })
+            .map(|(key, _)| key.to_string())
+            .collect();
+        for key in malformed {
+            table.remove(&key);
+            skipped.push(format!("[{}] 中的 {} 格式不正确，已原样保留", section, key));
+        }
+    }
+    if skipped.is_empty() {
+        return Err(error.into());
+    }
+    let manifest = Manifest::from_slice(sanitized.to_string().as_bytes()).map_err(|_| error)?;
+    Ok((manifest, skipped))
+}
+
+/// target 配置本身和其中的依赖表都是表；格式不正确的配置不删除也不修改
+fn is_well_formed_target_config(config: &Item) -> bool {
+    config.as_table_like().is_some_and(|config| {
+        ["dependencies", "dev-dependencies", "build-dependencies"]
+            .iter()
+            .all(|kind| config.get(kind).is_none_or(|deps| deps.is_table_like()))
+    })
+}
+
+/// 校验处理结果：能被 `cargo_toml` 解析，且没有新出现的悬空 features 引用
+///
+/// 输入中格式不正确、原样保留的部分（见 [`parse_manifest`]）不算校验失败
+fn validate_output(input: &str, output: &str) -> Result<(), ProcessError> {
+    let before = input.parse::<DocumentMut>()?;
+    let after = output.parse::<DocumentMut>()?;
+    parse_manifest(&after, output).map_err(|e| ProcessError::ValidationError(e.to_string()))?;
+    let existing = dangling_feature_refs(&before);
+    let introduced: Vec<String> = dangling_feature_refs(&after)
+        .into_iter()
+        .filter(|problem| !existing.contains(problem))
+        .collect();
+    if introduced.is_empty() {
+        Ok(())
+    } else {
+        Err(ProcessError::ValidationError(introduced.join("; ")))
+    }
+}
+
+/// features 中指向不存在的依赖或 feature 的条目
+fn dangling_feature_refs(doc: &DocumentMut) -> BTreeSet<String> {
+    let Some(features) = doc.get("features").and_then(Item::as_table_like) else {
+        return BTreeSet::new();
+    };
+    let deps = collect_surviving_deps(doc);
+    let mut problems = BTreeSet::new();
+    for (name, value) in features.iter() {
+        let Some(array) = value.as_array() else {
+            continue;
+        };
+        for item in array.iter().filter_map(|v| v.as_str()) {
+            let missing = if let Some(dep) = item.strip_prefix("dep:") {
+                !deps.contains(dep)
+            } else if let Some((dep, _)) = item.split_once('/') {
+                !deps.contains(dep.trim_end_matches('?'))
+            } else {
+                !features.contains_key(item) && !deps.contains(item)
+            };
+            if missing {
+                problems.insert(format!("features.{} 引用了不存在的 {}", name, item));
+            }
+        }
+    }
+    problems
+}
+
+/// 删除的依赖或 target 配置超过 `limit` 比例时报错，参数为处理前后的 (依赖数, target 配置数)
+fn check_removal_ratio(
+    before: (usize, usize),
+    after: (usize, usize),
+    limit: f64,
+) -> Result<(), ProcessError> {
+    let counts = [
+        ("依赖", before.0, after.0),
+        ("target 配置", before.1, after.1),
+    ];
+    for (what, total, remaining) in counts {
+        let removed = total.saturating_sub(remaining);
+        if total > 0 && removed as f64 / total as f64 > limit {
+            return Err(ProcessError::ExcessiveRemoval(
+                what.to_string(),
+                removed,
+                total,
+                limit,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 生成标记注释的前缀，用于识别已有的生成标记
+pub const GENERATED_HEADER_PREFIX: &str = "# Generated by pre ";
+
+/// 生成标记注释，例如 `# Generated by pre v0.1.0 (keep=linux) — do not edit manually`
+pub fn generated_header(filter: &TargetFilter) -> String {
+    let mut keep = if filter.keep_triples.is_empty() {
+        format!("keep={}", filter.preset.name())
+    } else {
+        format!("keep={}", filter.keep_triples.join(","))
+    };
+    if let (true, Some(arches)) = (filter.keep_triples.is_empty(), &filter.keep_arch) {
+        keep.push_str(&format!(", arch={}", arches.join(",")));
+    }
+    if let (true, Some(envs)) = (filter.keep_triples.is_empty(), &filter.keep_env) {
+        keep.push_str(&format!(", env={}", envs.join(",")));
+    }
+    if let (true, Some(oss)) = (filter.keep_triples.is_empty(), &filter.keep_os) {
+        keep.push_str(&format!(", os={}", oss.join(",")));
+    }
+    if let Some(features) = &filter.active_features {
+        let features: Vec<&str> = features.iter().map(String::as_str).collect();
+        keep.push_str(&format!(", features={}", features.join(",")));
+    }
+    format!(
+        "{}v{} ({}) — do not edit manually",
+        GENERATED_HEADER_PREFIX,
+        env!("CARGO_PKG_VERSION"),
+        keep
+    )
+}
+
+/// 在内容开头添加生成标记注释
+///
+/// 开头已有的生成标记会被替换而不是重复叠加，因此多次处理同一文件的结果不变
+pub fn apply_generated_header(content: &str, filter: &TargetFilter) -> String {
+    let mut body = content;
+    while body.starts_with(GENERATED_HEADER_PREFIX) {
+        body = body.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+    format!("{}\n{}", generated_header(filter), body)
 }
 
 /// 使用 cargo_toml 提取平台特定依赖，然后用 toml_edit 删除配置
-fn remove_non_linux_targets(doc: &mut DocumentMut, manifest: &Manifest) -> HashSet<String> {
+fn remove_non_linux_targets(
+    doc: &mut DocumentMut,
+    manifest: &Manifest,
+    options: &ProcessOptions,
+    on_dep_removed: &mut dyn FnMut(&str),
+    report: &mut ProcessReport,
+) -> (HashSet<String>, HashSet<String>) {
+    let filter = &options.filter;
     let mut removed_deps = HashSet::new();
     let mut kept_deps = HashSet::new();
+    // 被删除依赖第一次出现的 target 描述，用于分类
+    let mut removed_specs: HashMap<&String, &String> = HashMap::new();
 
-    // 收集普通 dependencies 中的所有依赖（仅 dependencies，不包括 dev/build）
-    // features 只能引用普通的 dependencies 和 optional dependencies
+    // build-dependencies 单独记录，用于提示自定义构建脚本可能受影响
+    let mut removed_build_deps = BTreeSet::new();
+    let mut kept_build_deps: HashSet<&String> = manifest.build_dependencies.keys().collect();
+
+    // 收集普通 dependencies 和 build-dependencies 中的所有依赖（不包括 dev）
+    // features 可以启用可选的 dependencies 和 build-dependencies，但不能引用 dev-dependencies
     let mut normal_deps = HashSet::new();
-    for (dep_name, _) in &manifest.dependencies {
+    for dep_name in manifest
+        .dependencies
+        .keys()
+        .chain(manifest.build_dependencies.keys())
+    {
         normal_deps.insert(dep_name.clone());
     }
 
     // 使用 cargo_toml 提取所有 target-specific 依赖
     for (target_spec, target_dep) in &manifest.target {
         // 检查这个 target 是否应该被删除
-        if should_remove_target_config(target_spec) {
-            // 收集此 target 下的 dependencies（候选删除列表）
-            // 只收集 dependencies，不收集 dev_dependencies 和 build_dependencies
-            for (dep_name, _) in &target_dep.dependencies {
+        if should_remove_target_config(target_spec, filter) {
+            // 收集此 target 下的 dependencies 和 build_dependencies（候选删除列表）
+            // 不收集 dev_dependencies
+            for dep_name in target_dep
+                .dependencies
+                .keys()
+                .chain(target_dep.build_dependencies.keys())
+            {
                 removed_deps.insert(dep_name.clone());
+                removed_specs.entry(dep_name).or_insert(target_spec);
             }
+            removed_build_deps.extend(target_dep.build_dependencies.keys());
         } else {
-            // 保留的 target，记录其 dependencies
-            for (dep_name, _) in &target_dep.dependencies {
+            // 保留的 target，记录其 dependencies 和 build_dependencies
+            for dep_name in target_dep
+                .dependencies
+                .keys()
+                .chain(target_dep.build_dependencies.keys())
+            {
                 kept_deps.insert(dep_name.clone());
             }
+            kept_build_deps.extend(target_dep.build_dependencies.keys());
+        }
+    }
+
+    removed_build_deps.retain(|dep| !kept_build_deps.contains(dep));
+    if let Some(script) = custom_build_script(doc) {
+        if !removed_build_deps.is_empty() {
+            let deps: Vec<&str> = removed_build_deps.iter().map(|d| d.as_str()).collect();
+            report.warnings.push(format!(
+                "构建脚本 {} 可能依赖已删除的 build-dependencies: {}",
+                script,
+                deps.join(", ")
+            ));
+        }
+    }
+
+    // 从 removed_deps 中移除那些在 normal_deps 中也存在的依赖；
+    // 默认（保留优先）同样移除保留的 target 配置中也存在的依赖，只有完全被删除的依赖才应该被标记
+    let remove_wins = options.conflict_resolution == ConflictResolution::RemoveWins;
+    let conflicting: BTreeSet<String> = if remove_wins {
+        removed_deps
+            .iter()
+            .filter(|dep| {
+                kept_deps.contains(*dep)
+                    && !normal_deps.contains(*dep)
+                    && !options.keep_deps.contains(*dep)
+            })
+            .cloned()
+            .collect()
+    } else {
+        BTreeSet::new()
+    };
+    // 依赖名为清单中的本地名，重命名的依赖（`win = { package = "windows-sys" }`）
+    // 按实际的包名查找已知平台依赖列表
+    let packages = dep_package_names(manifest);
+    let package_of = |dep: &str| packages.get(dep).map_or(dep, String::as_str).to_string();
+
+    // 顶层 [dependencies] 中的已知平台依赖同时声明在被删除的 target 配置下时，一并从顶层删除。
+    // 只处理已知平台依赖列表中的依赖，保留的 target 配置或 build-dependencies 仍在使用时不删除
+    let keeps_linux = filter.keeps_linux();
+    let top_level_platform: BTreeSet<String> = removed_deps
+        .iter()
+        .filter(|dep| {
+            keeps_linux
+                && options.known_deps.contains(&package_of(dep))
+                && !options.keep_deps.contains(*dep)
+                && manifest.dependencies.contains_key(*dep)
+                && !manifest.build_dependencies.contains_key(*dep)
+                && !kept_deps.contains(*dep)
+        })
+        .cloned()
+        .collect();
+    // 只声明在被删除的 target 配置中、又不是已知平台依赖的依赖，移到顶层后不再算作删除；
+    // 指定不删除的依赖总是移到顶层
+    let hoisted: BTreeSet<String> = removed_deps
+        .iter()
+        .filter(|dep| {
+            !normal_deps.contains(*dep)
+                && !kept_deps.contains(*dep)
+                && (options.keep_deps.contains(*dep)
+                    || (options.hoist_deps && !options.known_deps.contains(&package_of(dep))))
+        })
+        .cloned()
+        .collect();
+    let candidates = removed_deps.clone();
+    removed_deps.retain(|dep| {
+        (remove_wins || !kept_deps.contains(dep))
+            && (!normal_deps.contains(dep) || top_level_platform.contains(dep))
+            && !hoisted.contains(dep)
+            && !options.keep_deps.contains(dep)
+    });
+    report.ambiguous_deps = candidates
+        .into_iter()
+        .filter(|dep| !removed_deps.contains(dep) && !hoisted.contains(dep))
+        .collect();
+
+    // 顶层 [build-dependencies] 和 [dev-dependencies] 中的已知平台依赖（例如 Windows 上嵌入资源用的
+    // embed-resource）没有 target 条件也直接删除；cc 等跨平台的依赖不在列表中，不受影响。
+    // 保留的 target 配置仍在使用时不删除，--keep-dev-deps 时不处理 dev-dependencies
+    let mut top_level_tooling: Vec<(&str, Vec<String>)> = Vec::new();
+    for (kind, deps) in [
+        ("build-dependencies", &manifest.build_dependencies),
+        ("dev-dependencies", &manifest.dev_dependencies),
+    ] {
+        if !keeps_linux || (kind == "dev-dependencies" && options.keep_dev_deps) {
+            continue;
+        }
+        let names: Vec<String> = deps
+            .keys()
+            .filter(|dep| {
+                options.known_deps.contains(&package_of(dep))
+                    && !kept_deps.contains(*dep)
+                    && !options.keep_deps.contains(*dep)
+            })
+            .cloned()
+            .collect();
+        // features 不能引用 dev-dependencies，只有 build-dependencies 用于清理 features；
+        // dev-dependencies 的删除只记录在 ProcessReport::operations 和 removed_deps 中。
+        // 同名依赖仍声明在顶层 [dependencies] 中时，features 中的引用仍然有效
+        if kind == "build-dependencies" {
+            removed_deps.extend(
+                names
+                    .iter()
+                    .filter(|dep| !manifest.dependencies.contains_key(*dep))
+                    .cloned(),
+            );
+        }
+        if !names.is_empty() {
+            top_level_tooling.push((kind, names));
+        }
+    }
+
+    // 通知回调：此时的 removed_deps 只包含清单中真实声明且被完全删除的依赖
+    let mut declared_removed: Vec<&String> = removed_deps.iter().collect();
+    declared_removed.sort();
+    for dep in declared_removed {
+        on_dep_removed(dep);
+        let category = known_dep_category(&package_of(dep)).unwrap_or_else(|| {
+            removed_specs
+                .get(dep)
+                .map_or("other", |spec| target_spec_category(spec))
+        });
+        report
+            .removed_by_category
+            .entry(category.to_string())
+            .or_default()
+            .push(dep.clone());
+    }
+
+    // 添加已知的平台特定依赖（仅那些不在kept_deps和normal_deps中的）
+    // 这份列表是针对 Linux 整理的，保留其他平台时不使用
+    let mut speculative = HashSet::new();
+    for dep in options
+        .known_deps
+        .names()
+        .into_iter()
+        .filter(|_| keeps_linux)
+    {
+        if !kept_deps.contains(dep)
+            && !normal_deps.contains(dep)
+            && !options.keep_deps.contains(dep)
+            && removed_deps.insert(dep.to_string())
+        {
+            speculative.insert(dep.to_string());
+        }
+    }
+
+    // 使用 toml_edit 删除配置（保留格式和注释）
+
+    if !top_level_platform.is_empty() {
+        if let Some(table) = doc
+            .get_mut("dependencies")
+            .and_then(|t| t.as_table_like_mut())
+        {
+            for name in &top_level_platform {
+                table.remove(name);
+                log_debug!("删除顶层 [dependencies] 中的平台依赖 {}", name);
+                report.operations.push(Operation::RemoveDep {
+                    target: None,
+                    kind: "dependencies".to_string(),
+                    name: name.clone(),
+                });
+            }
+            if table.is_empty() {
+                doc.remove("dependencies");
+            }
         }
+        let names: Vec<&str> = top_level_platform.iter().map(String::as_str).collect();
+        report.warnings.push(format!(
+            "删除了只在被删除的 target 配置中使用的顶层平台依赖: {}",
+            names.join(", ")
+        ));
     }
 
-    // 从 removed_deps 中移除那些在 kept_deps 或 normal_deps 中也存在的依赖
-    // 只有完全被删除的依赖才应该被标记
-    removed_deps.retain(|dep| !kept_deps.contains(dep) && !normal_deps.contains(dep));
-
-    // 添加已知的平台特定依赖（仅那些不在kept_deps和normal_deps中的）
-    for dep in get_known_platform_deps() {
-        if !kept_deps.contains(&dep) && !normal_deps.contains(&dep) {
-            removed_deps.insert(dep);
+    for (kind, names) in &top_level_tooling {
+        if *kind == "build-dependencies" {
+            if let Some(script) = custom_build_script(doc) {
+                report.warnings.push(format!(
+                    "构建脚本 {} 可能依赖已删除的 build-dependencies: {}",
+                    script,
+                    names.join(", ")
+                ));
+            }
         }
+        if let Some(table) = doc.get_mut(kind).and_then(|t| t.as_table_like_mut()) {
+            for name in names {
+                table.remove(name);
+                log_debug!("删除顶层 [{}] 中的已知平台依赖 {}", kind, name);
+                report.operations.push(Operation::RemoveDep {
+                    target: None,
+                    kind: kind.to_string(),
+                    name: name.clone(),
+                });
+            }
+            if table.is_empty() {
+                doc.remove(kind);
+            }
+        }
+        report.warnings.push(format!(
+            "删除了顶层 [{}] 中的已知平台依赖: {}",
+            kind,
+            names.join(", ")
+        ));
     }
 
-    // 使用 toml_edit 删除配置（保留格式和注释）
-
-    // 处理 [target] 表下的子项
-    if let Some(target_table) = doc.get_mut("target").and_then(|t| t.as_table_mut()) {
+    // 处理 [target] 表下的子项：[target.'cfg(...)'] 表头、target.'cfg(...)'.xxx 点分键和
+    // target = { 'cfg(...)' = { ... } } 内联表都按表结构遍历
+    let mut hoisted_items: Vec<(&str, String, Item)> = Vec::new();
+    if let Some(target_table) = doc.get_mut("target").and_then(|t| t.as_table_like_mut()) {
         let keys_to_remove: Vec<String> = target_table
             .iter()
-            .filter_map(|(key, _)| {
-                if should_remove_target_config(key) {
+            .filter_map(|(key, config)| {
+                if should_remove_target_config(key, filter) && is_well_formed_target_config(config)
+                {
                     Some(key.to_string())
                 } else {
                     None
@@ -165,8 +2187,50 @@ fn remove_non_linux_targets(doc: &mut DocumentMut, manifest: &Manifest) -> HashS
             })
             .collect();
 
+        // 要移动的依赖先复制出来，删除 target 配置后再写入顶层依赖表；
+        // 同一个依赖声明在多个被删除的配置中时，使用文档中第一处的声明
+        for key in &keys_to_remove {
+            for kind in ["dependencies", "build-dependencies"] {
+                let Some(deps) = target_table
+                    .get(key)
+                    .and_then(|t| t.get(kind))
+                    .and_then(Item::as_table_like)
+                else {
+                    continue;
+                };
+                for (name, item) in deps.iter().filter(|(name, _)| hoisted.contains(*name)) {
+                    if hoisted_items
+                        .iter()
+                        .any(|(k, n, _)| *k == kind && n == name)
+                    {
+                        continue;
+                    }
+                    log_debug!("把 {} 从 target 配置 {} 移到顶层 [{}]", name, key, kind);
+                    report.operations.push(Operation::HoistDep {
+                        target: key.clone(),
+                        kind: kind.to_string(),
+                        name: name.to_string(),
+                    });
+                    hoisted_items.push((kind, name.to_string(), item.clone()));
+                }
+            }
+        }
+
         for key in &keys_to_remove {
-            target_table.remove(key);
+            if options.keep_dev_deps && has_dev_deps(target_table.get(key)) {
+                strip_runtime_deps(target_table, key, report);
+                continue;
+            }
+            if let Some(config) = target_table.remove(key) {
+                record_target_deps(&config, report);
+            }
+            log_info!("删除 target 配置 {}", key);
+            report.operations.push(Operation::RemoveTarget(key.clone()));
+        }
+
+        // 删除优先：从保留的 target 配置中删除同时出现在被删除配置下的依赖
+        if !conflicting.is_empty() {
+            remove_conflicting_deps(target_table, &conflicting, report);
         }
 
         // 如果 target 表为空，删除整个表
@@ -175,13 +2239,31 @@ fn remove_non_linux_targets(doc: &mut DocumentMut, manifest: &Manifest) -> HashS
         }
     }
 
-    // 处理直接的 [target.'cfg(...)'.xxx] 表
+    if !hoisted_items.is_empty() {
+        let mut names = Vec::new();
+        for (kind, name, item) in hoisted_items {
+            insert_top_level_dep(doc, kind, &name, item);
+            names.push(name);
+        }
+        names.sort();
+        names.dedup();
+        report.warnings.push(format!(
+            "把只在被删除的 target 配置中声明的依赖移到了顶层: {}",
+            names.join(", ")
+        ));
+        report.hoisted_deps = names;
+    }
+
+    // 处理键名本身以 "target." 开头的顶层项
+    // 解析得到的文档中不会出现这种键：[target.'cfg(...)'.xxx] 表头和 target.'cfg(...)'.xxx 点分键
+    // 都会被 toml_edit 拆分并嵌套到 target 表下，由上面的分支处理。
+    // 只有通过 API 直接插入含点号的键时才会走到这里，作为兜底保留
     let keys_to_remove: Vec<String> = doc
         .iter()
         .filter_map(|(key, _)| {
             if key.starts_with("target.") {
                 if let Some(cfg_part) = extract_cfg_from_target_key(key) {
-                    if should_remove_target_config(&cfg_part) {
+                    if should_remove_target_config(&cfg_part, filter) {
                         return Some(key.to_string());
                     }
                 }
@@ -191,101 +2273,348 @@ fn remove_non_linux_targets(doc: &mut DocumentMut, manifest: &Manifest) -> HashS
         .collect();
 
     for key in keys_to_remove {
-        doc.remove(&key);
+        if let Some(config) = doc.remove(&key) {
+            record_target_deps(&config, report);
+        }
+        log_info!("删除 target 配置 {}", key);
+        report.operations.push(Operation::RemoveTarget(key));
     }
 
-    removed_deps
+    (removed_deps, speculative)
 }
 
-fn extract_cfg_from_target_key(key: &str) -> Option<String> {
-    if let Some(rest) = key.strip_prefix("target.") {
-        // 匹配引号形式: 'cfg(...)' 或 "cfg(...)"
-        if rest.starts_with('\'') || rest.starts_with('"') {
-            let quote = rest.chars().next().unwrap();
-            if let Some(end_pos) = rest[1..].find(quote) {
-                return Some(rest[1..end_pos + 1].to_string());
+/// 处理 workspace 根清单中的平台依赖
+///
+/// 删除 `[workspace.target.'cfg(...)']` 下不保留的配置；`[workspace.dependencies]` 本身没有平台条件，
+/// 只删除已知平台依赖列表中（按实际包名）、且根清单自身没有使用的依赖。
+/// 由 [`process_workspace`] 处理时还要求没有成员仍在使用；单独处理根清单时无法知道成员的使用情况，
+/// 删除时给出警告。删除的依赖名加入 `removed_deps`，用于清理根清单中的 features
+fn remove_workspace_platform_deps(
+    doc: &mut DocumentMut,
+    options: &ProcessOptions,
+    removed_deps: &mut HashSet<String>,
+    on_dep_removed: &mut dyn FnMut(&str),
+    report: &mut ProcessReport,
+) {
+    let mut in_use = collect_surviving_deps(doc);
+    if let Some(member_deps) = &options.workspace_member_deps {
+        in_use.extend(member_deps.iter().cloned());
+    }
+    let Some(workspace) = doc.get_mut("workspace").and_then(|w| w.as_table_like_mut()) else {
+        return;
+    };
+
+    let mut removed = BTreeSet::new();
+    if let Some(targets) = workspace
+        .get_mut("target")
+        .and_then(|t| t.as_table_like_mut())
+    {
+        let keys: Vec<String> = targets
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| should_remove_target_config(key, &options.filter))
+            .collect();
+        for key in keys {
+            if let Some(config) = targets.remove(&key) {
+                for kind in ["dependencies", "build-dependencies"] {
+                    if let Some(deps) = config.get(kind).and_then(|d| d.as_table_like()) {
+                        removed.extend(deps.iter().map(|(name, _)| name.to_string()));
+                    }
+                }
+                record_target_deps(&config, report);
             }
+            report
+                .operations
+                .push(Operation::RemoveWorkspaceTarget(key));
         }
-        // 匹配无引号形式: cfg(...).xxx
-        if let Some(dot_pos) = rest.find('.') {
-            return Some(rest[..dot_pos].to_string());
+        if targets.is_empty() {
+            workspace.remove("target");
+        }
+    }
+
+    let mut platform_deps = Vec::new();
+    if options.filter.keeps_linux() {
+        if let Some(deps) = workspace
+            .get_mut("dependencies")
+            .and_then(|d| d.as_table_like_mut())
+        {
+            platform_deps = deps
+                .iter()
+                .filter(|(name, dep)| {
+                    let package = dep.get("package").and_then(|p| p.as_str()).unwrap_or(name);
+                    options.known_deps.contains(package)
+                        && !in_use.contains(*name)
+                        && !options.keep_deps.contains(*name)
+                })
+                .map(|(name, _)| name.to_string())
+                .collect();
+            for name in &platform_deps {
+                deps.remove(name);
+                report
+                    .operations
+                    .push(Operation::RemoveWorkspaceDep(name.clone()));
+            }
+            if deps.is_empty() {
+                workspace.remove("dependencies");
+            }
+        }
+    }
+    if !platform_deps.is_empty() {
+        let message = match options.workspace_member_deps {
+            Some(_) => "删除了 [workspace.dependencies] 中没有成员使用的平台依赖",
+            None => "删除了 [workspace.dependencies] 中的平台依赖（成员中对它们的 workspace = true 引用需要一并处理）",
+        };
+        report
+            .warnings
+            .push(format!("{}: {}", message, platform_deps.join(", ")));
+    }
+
+    removed.extend(platform_deps);
+    for name in removed {
+        if !in_use.contains(&name) {
+            on_dep_removed(&name);
+            removed_deps.insert(name);
         }
-        return Some(rest.to_string());
     }
-    None
 }
 
-/// 检查 cfg 表达式是否匹配任何 Linux 目标平台
-fn matches_any_linux_target(cfg_str: &str) -> bool {
-    // 快速检查：如果明确包含 Linux 相关的条件，直接返回 true
-    if contains_linux_condition(cfg_str) {
-        return true;
+/// 重命名的依赖：本地名 -> `package` 指定的包名，包括所有依赖表和 target 配置下的依赖表
+fn dep_package_names(manifest: &Manifest) -> HashMap<String, String> {
+    let tables = [
+        &manifest.dependencies,
+        &manifest.dev_dependencies,
+        &manifest.build_dependencies,
+    ]
+    .into_iter()
+    .chain(manifest.target.values().flat_map(|target| {
+        [
+            &target.dependencies,
+            &target.dev_dependencies,
+            &target.build_dependencies,
+        ]
+    }));
+
+    let mut packages = HashMap::new();
+    for table in tables {
+        for (name, dep) in table {
+            if let Some(package) = dep.package() {
+                packages.insert(name.clone(), package.to_string());
+            }
+        }
+    }
+    packages
+}
+
+/// 把依赖声明写入顶层的 `kind` 依赖表（不存在时创建），已有同名依赖时不覆盖
+///
+/// `[target.'cfg(...)'.dependencies.tokio]` 形式的子表转为内联表
+pub(crate) fn insert_top_level_dep(doc: &mut DocumentMut, kind: &str, name: &str, item: Item) {
+    let Ok(mut value) = item.into_value() else {
+        return;
+    };
+    value.decor_mut().clear();
+    let deps = doc
+        .as_table_mut()
+        .entry(kind)
+        .or_insert_with(toml_edit::table);
+    if let Some(deps) = deps.as_table_like_mut() {
+        if !deps.contains_key(name) {
+            deps.insert(name, Item::Value(value));
+        }
     }
+}
 
-    // 尝试解析 cfg 表达式
-    let expr = match Expression::parse(cfg_str) {
-        Ok(expr) => expr,
-        Err(_) => {
-            // 如果解析失败（可能因为包含自定义 cfg 标志），
-            // 再次检查是否包含 Linux 条件
-            if cfg_str.contains("target_os") && cfg_str.contains("linux") {
-                return true;
+/// [`ConflictResolution::RemoveWins`]：从保留的 target 配置的 dependencies 和 build-dependencies 中
+/// 删除指定的依赖，删除后变为空的依赖表和 target 配置一并删除
+fn remove_conflicting_deps(
+    target_table: &mut dyn toml_edit::TableLike,
+    names: &BTreeSet<String>,
+    report: &mut ProcessReport,
+) {
+    let mut emptied = Vec::new();
+    for (key, config) in target_table.iter_mut() {
+        let key = key.get().to_string();
+        let Some(config) = config.as_table_like_mut() else {
+            continue;
+        };
+        for kind in ["dependencies", "build-dependencies"] {
+            if let Some(deps) = config.get_mut(kind).and_then(|t| t.as_table_like_mut()) {
+                for name in names {
+                    if deps.remove(name).is_some() {
+                        log_debug!("删除保留的 target 配置 {} 中的冲突依赖 {}", key, name);
+                        report.operations.push(Operation::RemoveDep {
+                            target: Some(key.clone()),
+                            kind: kind.to_string(),
+                            name: name.clone(),
+                        });
+                    }
+                }
+                if deps.is_empty() {
+                    config.remove(kind);
+                }
             }
-            return is_linux_target_triple(cfg_str);
         }
+        if config.is_empty() {
+            emptied.push(key);
+        }
+    }
+    for key in emptied {
+        target_table.remove(&key);
+    }
+}
+
+/// 把随 target 配置一起删除的依赖记录到 [`ProcessReport::removed_deps`]
+fn record_target_deps(config: &Item, report: &mut ProcessReport) {
+    let Some(config) = config.as_table_like() else {
+        return;
     };
+    for (kind, deps) in config.iter() {
+        if let Some(deps) = deps.as_table_like() {
+            for (name, _) in deps.iter() {
+                report.removed_deps.insert(kind, name);
+            }
+        }
+    }
+}
 
-    // 获取所有 Linux 目标平台
-    let linux_targets = get_linux_targets();
+/// target 配置中是否有非空的 dev-dependencies，见 [`Processor::strip_dev_dependencies`]
+fn has_dev_deps(config: Option<&Item>) -> bool {
+    config
+        .and_then(|config| config.get("dev-dependencies"))
+        .and_then(Item::as_table_like)
+        .is_some_and(|deps| !deps.is_empty())
+}
 
-    // 检查表达式是否匹配任何 Linux 目标
-    for target in linux_targets.iter() {
-        if expr.eval(|pred| match pred {
-            Predicate::Target(tp) => tp.matches(*target),
-            Predicate::TargetFeature(_) => false,
-            Predicate::Test => false,
-            Predicate::DebugAssertions => false,
-            Predicate::ProcMacro => false,
-            Predicate::Feature(_) => false,
-            _ => false,
-        }) {
-            return true;
+/// 从 target 配置中删除 dependencies 和 build-dependencies，dev-dependencies 保留
+fn strip_runtime_deps(
+    targets: &mut dyn toml_edit::TableLike,
+    key: &str,
+    report: &mut ProcessReport,
+) {
+    let Some(config) = targets.get_mut(key).and_then(Item::as_table_like_mut) else {
+        return;
+    };
+    for kind in ["dependencies", "build-dependencies"] {
+        let Some(deps) = config.remove(kind) else {
+            continue;
+        };
+        for (name, _) in deps
+            .as_table_like()
+            .into_iter()
+            .flat_map(|deps| deps.iter())
+        {
+            report.operations.push(Operation::RemoveDep {
+                target: Some(key.to_string()),
+                kind: kind.to_string(),
+                name: name.to_string(),
+            });
         }
     }
+}
 
-    false
+fn extract_cfg_from_target_key(key: &str) -> Option<String> {
+    key.strip_prefix("target.")
+        .and_then(split_target_spec)
+        .map(Cow::into_owned)
 }
 
-/// 快速检查字符串是否包含明确的 Linux 相关条件
-fn contains_linux_condition(s: &str) -> bool {
-    // 检查是否包含明确的 Linux OS 声明
-    if s.contains("target_os") && s.contains("\"linux\"") {
-        return true;
+/// 从 `target.` 之后的部分提取 target 描述（cfg 表达式或 triple）
+///
+/// 双引号形式按 TOML 基本字符串处理转义（`\"`、`\\` 等），单引号形式原样返回；
+/// 无引号的 `cfg(...)` 按括号配对提取，cfg 内字符串中的括号和点号不计。
+/// 无法识别的写法（例如未闭合的引号或括号）返回 None
+fn split_target_spec(rest: &str) -> Option<Cow<'_, str>> {
+    // 三引号形式: '''cfg(...)''' 或 """cfg(...)"""
+    // TOML 不允许多行字符串作为键，cargo 也不接受，这里只做容错，避免按单引号处理提取出空字符串
+    for delim in ["'''", "\"\"\""] {
+        if let Some(inner) = rest.strip_prefix(delim) {
+            return inner.find(delim).map(|end| Cow::Borrowed(&inner[..end]));
+        }
     }
 
-    // 检查是否包含 unix family（在排除其他非 Linux Unix 系统后）
-    if s.contains("target_family") && s.contains("\"unix\"") {
-        // 但如果同时排除了 Linux 不支持的系统，那就是针对 Linux 的
-        if s.contains("not")
-            && (s.contains("macos")
-                || s.contains("ios")
-                || s.contains("android")
-                || s.contains("redox"))
-        {
-            return true;
+    // 字面字符串 'cfg(...)'，没有转义
+    if let Some(inner) = rest.strip_prefix('\'') {
+        return inner.find('\'').map(|end| Cow::Borrowed(&inner[..end]));
+    }
+
+    // 基本字符串 "cfg(...)"，内容可能包含转义的引号
+    if let Some(inner) = rest.strip_prefix('"') {
+        return unescape_basic_string(inner).map(Cow::Owned);
+    }
+
+    // 无引号形式: cfg(...).xxx 或 triple.xxx
+    if rest.starts_with("cfg(") {
+        return cfg_expr_len(rest).map(|len| Cow::Borrowed(&rest[..len]));
+    }
+    rest.split('.').next().map(Cow::Borrowed)
+}
+
+/// 读取基本字符串直到未转义的 `"`，返回转义后的内容；未闭合或转义无效时返回 None
+fn unescape_basic_string(inner: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => value.push(match chars.next()? {
+                'b' => '\u{8}',
+                't' => '\t',
+                'n' => '\n',
+                'f' => '\u{c}',
+                'r' => '\r',
+                'e' => '\u{1b}',
+                '"' => '"',
+                '\\' => '\\',
+                'u' => unicode_escape(&mut chars, 4)?,
+                'U' => unicode_escape(&mut chars, 8)?,
+                _ => return None,
+            }),
+            c => value.push(c),
         }
     }
+    None
+}
 
-    // 检查 cfg(unix) 简写形式
-    if s.contains("cfg(unix)") || (s.contains("unix") && !s.contains("target_")) {
-        return true;
+fn unicode_escape(chars: &mut std::str::Chars<'_>, digits: usize) -> Option<char> {
+    let hex: String = chars.take(digits).collect();
+    if hex.len() != digits {
+        return None;
     }
+    u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+}
 
-    false
+/// `cfg(...)` 的长度（到与 `cfg(` 配对的右括号为止），跳过其中字符串里的括号；未闭合时返回 None
+fn cfg_expr_len(s: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in s.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' => depth += 1,
+            ')' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
 }
 
 /// 检查是否是 Linux 目标 triple（仅 x86_64 和 riscv64）
-fn is_linux_target_triple(triple: &str) -> bool {
+pub(crate) fn is_linux_target_triple(triple: &str) -> bool {
     if let Some(target) = get_builtin_target_by_triple(triple) {
         // 必须是 Linux 系统
         if target.os != Some(cfg_expr::targets::Os::linux) {
@@ -295,134 +2624,330 @@ fn is_linux_target_triple(triple: &str) -> bool {
         return target.arch == cfg_expr::targets::Arch::x86_64
             || target.arch == cfg_expr::targets::Arch::riscv64;
     }
-    // 如果不是内置的 triple，检查是否同时包含 linux 和 (x86_64 或 riscv64) 关键词
-    triple.contains("linux") && (triple.contains("x86_64") || triple.contains("riscv64"))
+    // 不是内置的 triple 时按 arch-vendor-os-env 拆分判断
+    let parts = TripleParts::parse(triple);
+    parts.has_os("linux") && matches!(parts.target_arch(), "x86_64" | "riscv64")
 }
 
-fn should_remove_target_config(key: &str) -> bool {
+pub(crate) fn should_remove_target_config(key: &str, filter: &TargetFilter) -> bool {
     // 首先尝试识别并解析 cfg 表达式
-    let cfg_str = if key.starts_with("cfg(") {
+    let spec = if key.starts_with("cfg(") {
         // 直接的 cfg(...) 形式
-        key
+        Cow::Borrowed(key)
     } else if let Some(rest) = key.strip_prefix("target.") {
-        // target.'cfg(...)'... 形式，提取 cfg 部分
-        if rest.starts_with('\'') || rest.starts_with('"') {
-            let quote = rest.chars().next().unwrap();
-            if let Some(end_pos) = rest[1..].find(quote) {
-                &rest[1..end_pos + 1]
-            } else {
-                key
-            }
-        } else if let Some(dot_pos) = rest.find('.') {
-            &rest[..dot_pos]
-        } else {
-            rest
+        // target.'cfg(...)'... 形式，提取 cfg 部分；无法识别的写法保留
+        match split_target_spec(rest) {
+            Some(spec) => spec,
+            None => return false,
         }
     } else {
-        key
+        Cow::Borrowed(key)
     };
+    let cfg_str = spec.as_ref();
 
     // 特殊处理：cfg(any()) 总是为真，包含所有平台，应该保留
     if cfg_str == "cfg(any())" || cfg_str.trim() == "cfg(any())" {
         return false;
     }
 
-    // 尝试使用 cfg-expr 解析和评估
-    if cfg_str.starts_with("cfg(") || cfg_str.contains("target_") {
-        // 检查是否匹配任何 Linux 目标
-        let matches_linux = matches_any_linux_target(cfg_str);
-
-        // 如果匹配 Linux，保留；否则删除
-        return !matches_linux;
+    // 解析 cfg 表达式并针对保留的目标平台求值；无法解析的表达式无法判断，保留
+    if cfg_str.starts_with("cfg(") {
+        return cfg::parse(cfg_str).is_ok_and(|expr| !expr.matches(filter));
     }
 
-    // 对于非 cfg 表达式（可能是 target triple），检查是否是 Linux triple
-    !is_linux_target_triple(cfg_str)
+    // 其余的键是 target triple，与保留的 triple 比较，或按预设判断（见 TargetFilter::keeps_preset_triple）
+    if filter.keep_triples.is_empty() {
+        !filter.keeps_preset_triple(cfg_str)
+    } else {
+        !filter.keep_triples.iter().any(|triple| triple == cfg_str)
+    }
 }
 
 /// 获取已知的平台特定依赖列表
 /// 这个列表用于补充 cargo_toml 解析的结果
-fn get_known_platform_deps() -> HashSet<String> {
-    let platform_specific = vec![
-        // Windows
-        "windows-sys",
-        "winapi",
-        "anstyle-wincon",
+///
+/// 集合只在第一次调用时构建，批量处理多个文件时不会重复分配
+pub(crate) fn get_known_platform_deps() -> &'static HashSet<&'static str> {
+    &KNOWN_PLATFORM_DEPS
+}
+
+/// 已知的平台特定依赖，按平台分类：(分类名, 依赖列表)
+///
+/// 分类名也用于 [`ProcessReport::removed_by_category`]
+const KNOWN_PLATFORM_DEP_CATEGORIES: &[(&str, &[&str])] = &[
+    // Windows
+    (
         "windows",
-        "windows-core",
-        "windows-targets",
-        "windows-implement",
-        "windows-interface",
-        "windows-result",
-        "winreg",
-        "wio",
-        "winapi-util",
-        "ntapi",
-        // macOS/iOS
-        "cocoa",
-        "core-foundation",
-        "core-foundation-sys",
-        "core-graphics",
-        "core-graphics-types",
-        "objc",
-        "objc2",
-        "objc2-foundation",
-        "objc2-app-kit",
-        "objc2-ui-kit",
-        "objc2-core-image",
-        "objc-foundation",
-        "fsevent-sys",
-        "fsevents-sys",
-        "block",
-        "block2",
-        "dispatch",
-        "icrate",
-        "metal",
-        "core-video",
-        "mach",
-        "mach2",
-        // Android
-        "android-activity",
-        "android-properties",
-        "android_log-sys",
-        "android_logger",
-        "ndk",
-        "ndk-sys",
-        "ndk-context",
-        "ndk-glue",
-        "jni",
-        "jni-sys",
-        // WASM
-        "wasm-bindgen",
-        "wasm-bindgen-futures",
-        "wasm-bindgen-macro",
-        "js-sys",
-        "web-sys",
-        "web-time",
-        "console_error_panic_hook",
-        "tracing-web",
-        "gloo",
-        "gloo-utils",
-        "gloo-timers",
-        // BSD和其他Unix系统
-        "orbclient",
-        "redox_syscall",
-        "redox_users",
-        "wasip2",
-        "wasi",
-        "r-efi",
-        "r-efi-alloc",
-        // 其他平台特定
-        "hermit-abi",
-        "sgx_tstd",
-    ];
+        &[
+            "windows-sys",
+            "winapi",
+            "anstyle-wincon",
+            "windows",
+            "windows-core",
+            "windows-targets",
+            "windows-implement",
+            "windows-interface",
+            "windows-result",
+            "winreg",
+            "wio",
+            "winapi-util",
+            "ntapi",
+            "embed-resource",
+            "winres",
+            "winresource",
+        ],
+    ),
+    // macOS/iOS
+    (
+        "apple",
+        &[
+            "cocoa",
+            "core-foundation",
+            "core-foundation-sys",
+            "core-graphics",
+            "core-graphics-types",
+            "objc",
+            "objc2",
+            "objc2-foundation",
+            "objc2-app-kit",
+            "objc2-ui-kit",
+            "objc2-core-image",
+            "objc-foundation",
+            "fsevent-sys",
+            "fsevents-sys",
+            "block",
+            "block2",
+            "dispatch",
+            "icrate",
+            "metal",
+            "core-video",
+            "mach",
+            "mach2",
+        ],
+    ),
+    // Android
+    (
+        "android",
+        &[
+            "android-activity",
+            "android-properties",
+            "android_log-sys",
+            "android_logger",
+            "ndk",
+            "ndk-sys",
+            "ndk-context",
+            "ndk-glue",
+            "jni",
+            "jni-sys",
+        ],
+    ),
+    // WASM
+    (
+        "wasm",
+        &[
+            "wasm-bindgen",
+            "wasm-bindgen-futures",
+            "wasm-bindgen-macro",
+            "js-sys",
+            "web-sys",
+            "web-time",
+            "console_error_panic_hook",
+            "tracing-web",
+            "gloo",
+            "gloo-utils",
+            "gloo-timers",
+        ],
+    ),
+    // BSD和其他Unix系统，以及其他平台特定
+    (
+        "other",
+        &[
+            "orbclient",
+            "redox_syscall",
+            "redox_users",
+            "wasip2",
+            "wasi",
+            "r-efi",
+            "r-efi-alloc",
+            "hermit-abi",
+            "sgx_tstd",
+        ],
+    ),
+];
+
+static KNOWN_PLATFORM_DEPS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    KNOWN_PLATFORM_DEP_CATEGORIES
+        .iter()
+        .flat_map(|(_, deps)| deps.iter().copied())
+        .collect()
+});
+
+/// 已知平台特定依赖所属的分类
+fn known_dep_category(dep: &str) -> Option<&'static str> {
+    KNOWN_PLATFORM_DEP_CATEGORIES
+        .iter()
+        .find(|(_, deps)| deps.contains(&dep))
+        .map(|(category, _)| *category)
+}
+
+/// target 描述中的非 Linux 平台关键字，按平台分类：(分类名, 关键字列表)
+const NON_LINUX_OS_KEYWORDS: &[(&str, &[&str])] = &[
+    ("windows", &["windows"]),
+    (
+        "apple",
+        &[
+            "macos", "ios", "apple", "tvos", "watchos", "visionos", "darwin",
+        ],
+    ),
+    ("android", &["android"]),
+    ("wasm", &["wasm", "wasi"]),
+];
+
+static NON_LINUX_OS_LIST: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
+    NON_LINUX_OS_KEYWORDS
+        .iter()
+        .flat_map(|(_, keywords)| keywords.iter().copied())
+        .collect()
+});
+
+static KEPT_LINUX_TRIPLES: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
+    get_linux_targets()
+        .iter()
+        .map(|target| target.triple.as_str())
+        .collect()
+});
+
+// 以下访问函数暴露本 crate 实际使用的分类数据，下游工具和测试应直接引用，
+// 不要自行维护可能与之不一致的副本
+
+/// 非 Linux 平台关键字（`windows`、`macos`、`wasm` 等），用于给被删除的依赖分类
+///
+/// 不在 [`known_platform_dep_table`] 中的依赖按所在 target 配置中的这些关键字归入
+/// [`ProcessReport::removed_by_category`] 的分类
+pub fn non_linux_os_list() -> &'static [&'static str] {
+    &NON_LINUX_OS_LIST
+}
+
+/// 已知的平台特定依赖：(分类名, 依赖列表)，分类名与 [`ProcessReport::removed_by_category`] 一致
+///
+/// [`is_known_platform_dep`] 和 [`KnownDeps`] 的内置列表都由这张表生成
+pub fn known_platform_dep_table() -> &'static [(&'static str, &'static [&'static str])] {
+    KNOWN_PLATFORM_DEP_CATEGORIES
+}
+
+/// 激进模式下判断依赖名是否平台特定的名称片段，见 [`looks_platform_specific`]
+pub fn platform_name_tokens() -> &'static [&'static str] {
+    PLATFORM_NAME_TOKENS
+}
+
+/// 默认保留的 Linux 目标 triple（x86_64 和 riscv64）
+///
+/// 默认的 [`TargetFilter`] 在这些目标上对 cfg 表达式求值，见 [`is_target_removed`]
+pub fn kept_linux_triples() -> &'static [&'static str] {
+    &KEPT_LINUX_TRIPLES
+}
+
+/// 依赖名是否在内置的已知平台特定依赖列表中（[`known_platform_dep_table`] 中的任一分类）
+///
+/// 这些依赖只在 Windows、Apple、Android、WASM 等平台上有用，出现在被删除的 target 配置之外时
+/// 也会被当作平台特定依赖删除；不包括 `--known-deps` 的修改，需要时使用 [`KnownDeps::contains`]
+pub fn is_known_platform_dep(name: &str) -> bool {
+    get_known_platform_deps().contains(name)
+}
+
+/// 按过滤条件判断 target 配置是否会被删除
+///
+/// `spec` 可以是 `cfg(...)` 表达式、target triple，或 `.cargo/config.toml` 中的 `target.<spec>` 键；
+/// 无法解析的 cfg 表达式无法判断，返回 false（保留）
+pub fn is_target_removed(spec: &str, filter: &TargetFilter) -> bool {
+    should_remove_target_config(spec, filter)
+}
+
+/// 根据 target 描述中的平台关键字推断分类，用于不在已知列表中的依赖
+fn target_spec_category(spec: &str) -> &'static str {
+    NON_LINUX_OS_KEYWORDS
+        .iter()
+        .find(|(_, keywords)| keywords.iter().any(|k| spec.contains(k)))
+        .map_or("other", |(category, _)| *category)
+}
+
+/// 交叉检查 cargo_toml 与 toml_edit 看到的 features 是否一致
+///
+/// `clean_features` 只处理文档中的 `[features]` 表，而可选依赖会产生未在表中声明的隐式 feature，
+/// 返回的每一条信息都描述一处不一致，便于发现可能未被正确清理的 feature。
+/// 处理时表中 feature 名的不一致和被删除的可选依赖产生的隐式 feature 会记录在
+/// [`ProcessReport::warnings`] 中
+pub fn check_feature_consistency(doc: &DocumentMut, manifest: &Manifest) -> Vec<String> {
+    let mut messages = feature_key_mismatches(doc, manifest);
+    for name in implicit_optional_features(doc, manifest) {
+        messages.push(format!(
+            "可选依赖 `{}` 产生了隐式 feature，[features] 表中未声明",
+            name
+        ));
+    }
+    messages
+}
+
+fn doc_feature_names(doc: &DocumentMut) -> BTreeSet<&str> {
+    doc.get("features")
+        .and_then(|f| f.as_table_like())
+        .map(|t| t.iter().map(|(key, _)| key).collect())
+        .unwrap_or_default()
+}
+
+/// cargo_toml 与文档中 `[features]` 表的 feature 名不一致之处
+fn feature_key_mismatches(doc: &DocumentMut, manifest: &Manifest) -> Vec<String> {
+    let doc_features = doc_feature_names(doc);
+    let manifest_features: BTreeSet<&str> = manifest.features.keys().map(String::as_str).collect();
+
+    let mut messages = Vec::new();
+    for name in manifest_features.difference(&doc_features) {
+        messages.push(format!(
+            "cargo_toml 识别到 feature `{}`，但 [features] 表中不存在",
+            name
+        ));
+    }
+    for name in doc_features.difference(&manifest_features) {
+        messages.push(format!("[features] 表中的 `{}` 未被 cargo_toml 识别", name));
+    }
+    messages
+}
 
-    platform_specific.iter().map(|s| s.to_string()).collect()
+/// 可选依赖产生的隐式 feature（未使用 dep: 语法引用时），按名称排序
+fn implicit_optional_features(doc: &DocumentMut, manifest: &Manifest) -> Vec<String> {
+    let doc_features = doc_feature_names(doc);
+    let resolved = cargo_toml::features::Resolver::new().parse(manifest);
+    let mut implicit: Vec<String> = resolved
+        .features
+        .values()
+        .filter(|feature| !feature.explicit && !doc_features.contains(feature.key))
+        .map(|feature| feature.key.to_string())
+        .collect();
+    implicit.sort();
+    implicit
 }
 
-fn clean_features(doc: &mut DocumentMut, removed_deps: &HashSet<String>) {
+/// 清理顶层 `[features]` 中引用被删除依赖的项
+///
+/// Cargo 的 features 只能声明在顶层；其他位置的 `features` 键（例如误写在 target 下的）不属于
+/// features 表，不做处理
+fn clean_features(
+    doc: &mut DocumentMut,
+    removed_deps: &HashSet<String>,
+    speculative: &HashSet<String>,
+    mode: FeatureMode,
+    prune_empty: bool,
+    remove_empty: bool,
+    report: &mut ProcessReport,
+) {
     if let Some(features) = doc.get_mut("features").and_then(|f| f.as_table_like_mut()) {
         let mut features_to_update: Vec<(String, Vec<String>)> = Vec::new();
+        let removed_deps = &normalized_names(removed_deps);
+        let speculative = &normalized_names(speculative);
+
+        // 显式声明的 feature 名，纯名称形式的引用优先指向它们
+        let declared_features: HashSet<String> =
+            features.iter().map(|(name, _)| name.to_string()).collect();
 
         // 遍历每个feature
         for (name, value) in features.iter() {
@@ -430,19 +2955,25 @@ fn clean_features(doc: &mut DocumentMut, removed_deps: &HashSet<String>) {
 
             if let Some(array) = value.as_array() {
                 let mut new_items = Vec::new();
-                let mut modified = false;
+                let mut removed_items = Vec::new();
 
                 for item in array.iter() {
                     if let Some(s) = item.as_str() {
-                        if !should_remove_feature_item(s, removed_deps) {
+                        if !should_remove_feature_item(s, removed_deps, &declared_features) {
                             new_items.push(s.to_string());
                         } else {
-                            modified = true;
+                            if should_remove_feature_item(s, speculative, &declared_features) {
+                                report.speculative_removals.insert(s.to_string());
+                            }
+                            removed_items.push(s.to_string());
                         }
                     }
                 }
 
-                if modified {
+                if !removed_items.is_empty() {
+                    report
+                        .modified_features
+                        .insert(name_str.clone(), removed_items);
                     // 保留 feature，即使变成空数组
                     // 例如 wincon = [] 或 std = []
                     features_to_update.push((name_str, new_items));
@@ -450,21 +2981,335 @@ fn clean_features(doc: &mut DocumentMut, removed_deps: &HashSet<String>) {
             }
         }
 
-        // 更新修改后的features（包括空的）
-        for (name, items) in features_to_update {
-            let mut new_array = Array::new();
+        if prune_empty {
+            prune_emptied_features(&*features, &mut features_to_update, report);
+        }
+
+        let mut emptied: Vec<String> = features_to_update
+            .iter()
+            .filter(|(_, items)| items.is_empty())
+            .map(|(name, _)| name.clone())
+            .collect();
+        emptied.sort();
+        report.emptied_features = emptied;
+
+        if mode == FeatureMode::ReportOnly {
+            return;
+        }
+
+        for (feature, items) in &report.modified_features {
             for item in items {
-                new_array.push(item);
+                log_debug!("删除 features.{} 中的条目 {}", feature, item);
+                report.operations.push(Operation::StripFeatureItem {
+                    feature: feature.clone(),
+                    item: item.clone(),
+                });
+            }
+        }
+
+        // 在原数组上删除条目（可能变为空数组），保留的条目的格式和注释不变
+        for (name, _) in features_to_update {
+            let Some(removed) = report.modified_features.get(&name) else {
+                continue;
+            };
+            if let Some(array) = features.get_mut(&name).and_then(Item::as_array_mut) {
+                retain_feature_items(array, |item| !removed.iter().any(|r| r == item));
+            }
+        }
+
+        if remove_empty {
+            for name in &report.emptied_features {
+                features.remove(name);
+                log_info!("删除变为空的 feature {}", name);
+                report
+                    .operations
+                    .push(Operation::RemoveFeature(name.clone()));
+            }
+            if features.is_empty() {
+                doc.remove("features");
+            }
+        }
+    }
+}
+
+/// 可以声明 `required-features` 的 Cargo target 段
+const CARGO_TARGET_SECTIONS: [&str; 4] = ["bin", "example", "test", "bench"];
+
+/// 清理 `[[bin]]`、`[[example]]`、`[[test]]`、`[[bench]]` 的 `required-features` 中
+/// 对已删除的 feature 和依赖的引用
+///
+/// 默认只删除这些条目，数组变为空时删除 `required-features` 键；`remove_targets` 为 true 时
+/// 删除整个 target 条目，段中没有剩余条目时删除整个段
+fn clean_required_features(
+    doc: &mut DocumentMut,
+    removed_deps: &HashSet<String>,
+    remove_targets: bool,
+    report: &mut ProcessReport,
+) {
+    let removed_features: HashSet<&str> = report
+        .operations
+        .iter()
+        .filter_map(|op| match op {
+            Operation::RemoveFeature(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let declared_features: HashSet<String> = doc
+        .get("features")
+        .and_then(Item::as_table_like)
+        .map(|features| features.iter().map(|(name, _)| name.to_string()).collect())
+        .unwrap_or_default();
+    let removed_deps = &normalized_names(removed_deps);
+
+    let mut operations = Vec::new();
+    for section in CARGO_TARGET_SECTIONS {
+        let Some(targets) = doc.get_mut(section).and_then(Item::as_array_of_tables_mut) else {
+            continue;
+        };
+
+        let mut unbuildable = Vec::new();
+        for (index, target) in targets.iter_mut().enumerate() {
+            let label = match target.get("name").and_then(Item::as_str) {
+                Some(name) => format!("[[{}]] {}", section, name),
+                None => format!("[[{}]] 第 {} 项", section, index + 1),
+            };
+            let Some(array) = target
+                .get_mut("required-features")
+                .and_then(Item::as_array_mut)
+            else {
+                continue;
+            };
+            let dead: Vec<String> = array
+                .iter()
+                .filter_map(|v| v.as_str())
+                .filter(|item| {
+                    removed_features.contains(item)
+                        || should_remove_feature_item(item, removed_deps, &declared_features)
+                })
+                .map(str::to_string)
+                .collect();
+            if dead.is_empty() {
+                continue;
+            }
+
+            if remove_targets {
+                report.warnings.push(format!(
+                    "{} 的 required-features 引用了已删除的 {}，已删除该条目",
+                    label,
+                    dead.join(", ")
+                ));
+                report.removed_cargo_targets.push(label);
+                unbuildable.push(index);
+                continue;
+            }
+
+            retain_feature_items(array, |item| !dead.iter().any(|d| d == item));
+            if array.is_empty() {
+                target.remove("required-features");
+            }
+            for item in &dead {
+                operations.push(Operation::StripRequiredFeature {
+                    section: section.to_string(),
+                    index,
+                    item: item.clone(),
+                });
+            }
+            report.stripped_required_features.insert(label, dead);
+        }
+
+        // 从后往前删除，记录的下标在重放时仍然有效
+        for index in unbuildable.into_iter().rev() {
+            targets.remove(index);
+            operations.push(Operation::RemoveCargoTarget {
+                section: section.to_string(),
+                index,
+            });
+        }
+        if targets.is_empty() {
+            doc.remove(section);
+        }
+    }
+    report.operations.extend(operations);
+}
+
+/// 删除数组中 `keep` 返回 false 的字符串条目，保留其余条目的格式和注释
+///
+/// 多行数组中，条目同一行的注释在 toml_edit 中属于下一个条目的前缀（或数组末尾的 trailing），
+/// 被删除的条目自己的注释和它上方的注释行一起删除，前一个保留的条目的行尾注释移到下一个保留的条目上
+pub(crate) fn retain_feature_items(array: &mut Array, mut keep: impl FnMut(&str) -> bool) {
+    if array.iter().all(|v| v.as_str().is_none_or(&mut keep)) {
+        return;
+    }
+
+    // 按第一个换行拆分：前一部分是上一行的行尾，后一部分是本条目上方的注释行和缩进
+    fn split_line_end(s: &str) -> (&str, &str) {
+        match s.find('\n') {
+            Some(i) => s.split_at(i + 1),
+            None => ("", s),
+        }
+    }
+    let prefix_of = |value: &Value| {
+        value
+            .decor()
+            .prefix()
+            .and_then(|p| p.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+
+    let first_prefix = array.get(0).map(prefix_of).unwrap_or_default();
+    let trailing = array.trailing().as_str().unwrap_or("").to_string();
+    let trailing_comma = array.trailing_comma();
+    let last_suffix = array
+        .iter()
+        .last()
+        .filter(|value| value.as_str().is_some_and(|v| !keep(v)))
+        .map(|value| {
+            value
+                .decor()
+                .suffix()
+                .and_then(|s| s.as_str())
+                .unwrap_or("")
+                .to_string()
+        });
+    let mut kept = Vec::new();
+    // 被删除的条目之前的行尾（属于上一个保留的条目或 `[`）
+    let mut pending: Option<String> = None;
+    for (index, mut value) in std::mem::take(array).into_iter().enumerate() {
+        let prefix = prefix_of(&value);
+        if value.as_str().is_none_or(&mut keep) {
+            if let Some(line_end) = pending.take() {
+                let (_, rest) = split_line_end(&prefix);
+                value
+                    .decor_mut()
+                    .set_prefix(format!("{}{}", line_end, rest));
+            }
+            if kept.is_empty() && index > 0 {
+                let prefix = prefix_of(&value);
+                if !prefix.contains('\n') {
+                    value.decor_mut().set_prefix(first_prefix.clone());
+                }
+            }
+            kept.push(value);
+        } else if pending.is_none() {
+            pending = Some(split_line_end(&prefix).0.to_string());
+        }
+    }
+
+    let mut trailing = match pending {
+        Some(line_end) => format!("{}{}", line_end, split_line_end(&trailing).1),
+        None => trailing,
+    };
+    if kept.is_empty() && trailing.trim().is_empty() {
+        trailing.clear();
+    }
+    // 最后一个条目被删除时，它与 `]` 之间的空白移到新的最后一个条目上
+    if let (Some(last), Some(suffix)) = (kept.last_mut(), last_suffix) {
+        last.decor_mut().set_suffix(suffix);
+    }
+    array.extend(kept);
+    array.set_trailing(trailing);
+    array.set_trailing_comma(trailing_comma && !array.is_empty());
+}
+
+/// 删除对清理后变为空数组的 feature 的纯名称引用
+///
+/// 例如 `win = ["dep:windows-sys"]` 被清空后，`default = ["win"]` 中的 `win` 已经不启用任何东西。
+/// 删除引用可能让更多 feature 变空，因此重复直到不再有新的 feature 被清空。
+/// 原本就是空数组的 feature（例如 `std = []`）不受影响，feature 定义本身也保留
+fn prune_emptied_features(
+    features: &dyn toml_edit::TableLike,
+    updates: &mut Vec<(String, Vec<String>)>,
+    report: &mut ProcessReport,
+) {
+    let mut current: BTreeMap<String, Vec<String>> = features
+        .iter()
+        .filter_map(|(name, value)| {
+            let items = value
+                .as_array()?
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect();
+            Some((name.to_string(), items))
+        })
+        .collect();
+    for (name, items) in updates.iter() {
+        current.insert(name.clone(), items.clone());
+    }
+
+    let mut emptied: HashSet<String> = updates
+        .iter()
+        .filter(|(_, items)| items.is_empty())
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut changed = BTreeSet::new();
+    loop {
+        let mut newly_emptied = Vec::new();
+        for (name, items) in current.iter_mut() {
+            let mut dropped = Vec::new();
+            items.retain(|item| {
+                let keep = !emptied.contains(item);
+                if !keep {
+                    dropped.push(item.clone());
+                }
+                keep
+            });
+            if !dropped.is_empty() {
+                report
+                    .modified_features
+                    .entry(name.clone())
+                    .or_default()
+                    .extend(dropped);
+                if items.is_empty() {
+                    newly_emptied.push(name.clone());
+                }
+                changed.insert(name.clone());
             }
-            features.insert(&name, Item::Value(Value::Array(new_array)));
         }
+        if newly_emptied.is_empty() {
+            break;
+        }
+        emptied.extend(newly_emptied);
+    }
+
+    for name in changed {
+        let items = current.remove(&name).unwrap_or_default();
+        match updates.iter_mut().find(|(updated, _)| *updated == name) {
+            Some((_, existing)) => *existing = items,
+            None => updates.push((name, items)),
+        }
+    }
+}
+
+/// 依赖名的规范形式：`_` 换成 `-`，大小写不变
+///
+/// 与 Cargo 对包名的处理一致：区分大小写，`foo_bar` 和 `foo-bar` 视为同一个依赖
+fn normalize_crate_name(name: &str) -> Cow<'_, str> {
+    if name.contains('_') {
+        Cow::Owned(name.replace('_', "-"))
+    } else {
+        Cow::Borrowed(name)
     }
 }
 
-fn should_remove_feature_item(item: &str, removed_deps: &HashSet<String>) -> bool {
+/// 规范形式的依赖名集合，用于 [`should_remove_feature_item`]
+fn normalized_names(deps: &HashSet<String>) -> HashSet<String> {
+    deps.iter()
+        .map(|dep| normalize_crate_name(dep).into_owned())
+        .collect()
+}
+
+/// `removed_deps` 为 [`normalized_names`] 得到的规范形式，条目中的依赖名同样规范化后比较
+fn should_remove_feature_item(
+    item: &str,
+    removed_deps: &HashSet<String>,
+    declared_features: &HashSet<String>,
+) -> bool {
+    let removed = |name: &str| removed_deps.contains(normalize_crate_name(name).as_ref());
+
     // 检查是否是 dep:xxx 形式（明确的依赖引用）
     if let Some(dep_name) = item.strip_prefix("dep:") {
-        return removed_deps.contains(&dep_name.to_lowercase()) || removed_deps.contains(dep_name);
+        return removed(dep_name);
     }
 
     // 检查是否是 crate/feature 或 crate?/feature 形式（依赖的 feature）
@@ -472,17 +3317,85 @@ fn should_remove_feature_item(item: &str, removed_deps: &HashSet<String>) -> boo
         if let Some(crate_part) = item.split('/').next() {
             // 去除可选依赖标记 '?'
             let crate_name = crate_part.trim_end_matches('?');
-            if removed_deps.contains(&crate_name.to_lowercase())
-                || removed_deps.contains(crate_name)
-            {
+            if removed(crate_name) {
                 return true;
             }
         }
         return false;
     }
 
-    // 对于纯名称形式，检查是否是被删除的依赖包
-    // 如果在 removed_deps 中，说明这是对依赖包的隐式引用，应该删除
+    // 对于纯名称形式，如果 [features] 中声明了同名 feature，这是对 feature 的引用，保留
+    // （例如名为 tokio 的 feature，即使 tokio 依赖被删除）
+    if declared_features.contains(item) {
+        return false;
+    }
+
+    // 否则检查是否是被删除的依赖包
+    // 如果在 removed_deps 中，说明这是对可选依赖隐式 feature 的引用，应该删除
     // 否则认为是对其他 feature 的引用，保留
-    removed_deps.contains(&item.to_lowercase()) || removed_deps.contains(item)
+    removed(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 已知平台依赖集合和分类数据只构建一次，每次调用返回同一个集合
+    #[test]
+    fn known_dep_sets_are_built_once() {
+        let first = get_known_platform_deps();
+        for _ in 0..10_000 {
+            assert!(std::ptr::eq(first, get_known_platform_deps()));
+        }
+        assert!(std::ptr::eq(non_linux_os_list(), non_linux_os_list()));
+        assert!(std::ptr::eq(kept_linux_triples(), kept_linux_triples()));
+    }
+
+    /// 公开的分类数据访问函数返回的就是内部使用的数据，分类结果一致
+    #[test]
+    fn accessors_expose_internal_tables() {
+        assert_eq!(known_platform_dep_table(), KNOWN_PLATFORM_DEP_CATEGORIES);
+        assert_eq!(non_linux_os_list(), NON_LINUX_OS_LIST.as_slice());
+        assert_eq!(platform_name_tokens(), PLATFORM_NAME_TOKENS);
+
+        for (category, deps) in known_platform_dep_table() {
+            for dep in *deps {
+                assert!(is_known_platform_dep(dep));
+                assert_eq!(known_dep_category(dep), Some(*category));
+            }
+        }
+
+        let kept: Vec<&str> = get_linux_targets()
+            .iter()
+            .map(|target| target.triple.as_str())
+            .collect();
+        assert_eq!(kept_linux_triples(), kept.as_slice());
+        let filter = TargetFilter::default();
+        for triple in kept_linux_triples() {
+            assert!(!is_target_removed(triple, &filter), "{}", triple);
+        }
+        // 已知的 Windows triple：不在保留列表中，按平台关键字归入 windows 分类
+        let windows = "x86_64-pc-windows-msvc";
+        assert!(!kept_linux_triples().contains(&windows));
+        assert!(is_target_removed(windows, &filter));
+        assert!(non_linux_os_list().contains(&"windows"));
+        assert_eq!(target_spec_category(windows), "windows");
+        assert!(is_known_platform_dep("windows-sys"));
+    }
+
+    /// 名称按 `-`/`_` 切分后整段匹配，不区分大小写；只是包含片段的名称不算
+    #[test]
+    fn looks_platform_specific_matches_whole_tokens() {
+        for name in [
+            "my-windows-helper",
+            "objc2-vendored",
+            "WinAPI-x",
+            "wasm_bindgen_shim",
+        ] {
+            assert!(looks_platform_specific(name), "{}", name);
+        }
+        for name in ["winnow", "window-manager", "cc", "iostream", "log"] {
+            assert!(!looks_platform_specific(name), "{}", name);
+        }
+    }
 }
@@ -1,21 +1,1267 @@
+use pre::{
+    Cache, Config, ConflictResolution, FeatureMode, KeepTarget, KnownDeps, Processor, StripMode,
+    TargetFilter, CONFIG_FILE_NAME,
+};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+/// `--version-json` 列出的功能，新增选项或模式时同步更新
+const CAPABILITIES: &[&str] = &[
+    "directory-mode",
+    "in-place",
+    "cargo-config",
+    "config-file",
+    "init-config",
+    "header",
+    "dry-run",
+    "check",
+    "keep-rustc-host",
+    "keep-bare-metal",
+    "keep-windows",
+    "keep-macos",
+    "keep-wasm",
+    "platform",
+    "keep-arch",
+    "keep-env",
+    "keep-os",
+    "keep-target",
+    "active-features",
+    "feature-cleaning",
+    "comment-out",
+    "conflict-resolution",
+    "prune-empty-features",
+    "remove-empty-features",
+    "keep-dev-deps",
+    "hoist-deps",
+    "remove-unbuildable-targets",
+    "prune-patches",
+    "require-content",
+    "validate",
+    "max-removal-ratio",
+    "aggressive",
+    "no-speculative",
+    "known-deps",
+    "platform-deps-file",
+    "strip-dep",
+    "keep-dep",
+    "only-if-present",
+    "report-only-changed",
+    "no-follow-symlinks",
+    "no-atomic",
+    "skip-invalid",
+    "cache",
+    "record-ops",
+    "dump-document",
+    "verbose",
+    "quiet",
+    "stdio",
+    #[cfg(feature = "path-deps")]
+    "resolve-path-deps",
+    #[cfg(feature = "serde")]
+    "json",
+    #[cfg(feature = "logging")]
+    "logging",
+];
+
+/// 输出版本和支持的功能，供编排工具在使用某个选项前检测是否可用
+fn print_version_json() {
+    let capabilities: Vec<String> = CAPABILITIES.iter().map(|c| format!("\"{}\"", c)).collect();
+    println!(
+        "{{\"version\":\"{}\",\"capabilities\":[{}]}}",
+        env!("CARGO_PKG_VERSION"),
+        capabilities.join(",")
+    );
+}
+
+fn print_usage(program: &str) {
+    eprintln!("用法: {} [选项] <input.toml> [output.toml]", program);
+    eprintln!("      {} [选项] <目录>", program);
+    eprintln!("      {} [选项] --in-place <input.toml|目录>...", program);
+    eprintln!("      {} init-config [--force]", program);
+    eprintln!("  如果未指定output.toml，将覆盖原文件");
+    eprintln!("  input.toml 为 - 时从标准输入读取（默认输出到标准输出），output.toml 为 - 时输出到标准输出");
+    eprintln!("  输入为目录时递归处理其中所有 Cargo.toml（原地覆盖）");
+    eprintln!("  --in-place 时所有参数都是输入，逐个原地覆盖，某个文件失败时继续处理其他文件");
+    eprintln!();
+    eprintln!("选项:");
+    eprintln!("  --version-json       以 JSON 输出版本和支持的功能");
+    eprintln!("  --config <pre.toml>  读取配置文件");
+    eprintln!("  --header             在输出开头添加生成标记注释");
+    eprintln!("  --dry-run            不写入文件，输出处理前后的 unified diff");
+    eprintln!("  --check              不写入文件，仍有需要删除的内容时列出并以状态 1 退出");
+    eprintln!(
+        "  --no-atomic          直接覆盖输出文件，不经过临时文件（用于不支持重命名覆盖的文件系统）"
+    );
+    eprintln!("  --no-speculative     有 feature 条目只因已知平台依赖列表被删除时报错");
+    eprintln!("  --known-deps <path>  合并已知平台依赖列表的修改文件（可重复，后面的优先）");
+    eprintln!("  --platform-deps-file <path>");
+    eprintln!("                       同 --known-deps，每行一个或 TOML 的 platform-deps 数组");
+    eprintln!("  --strip-dep <name>   从所有依赖表中删除指定依赖（可重复）");
+    eprintln!("  --keep-dep <name>    不删除指定依赖，即使它在已知平台依赖列表中（可重复）");
+    eprintln!("  --aggressive         按名称推测删除顶层的平台特定依赖（可能误删）");
+    eprintln!("  --keep <linux|windows|macos|wasm|rustc-host|bare-metal>");
+    eprintln!("                       保留的平台，macos 包括 iOS 等所有 Apple 平台，");
+    eprintln!("                       rustc-host 为当前工具链的默认目标，");
+    eprintln!("                       bare-metal 为 target_os = \"none\" 的裸机目标");
+    eprintln!("  --platform <name>    同 --keep");
+    eprintln!("  --keep-target <triple>");
+    eprintln!("                       只保留能在这些 triple 上启用的 target 配置（可重复）");
+    eprintln!(
+        "  --keep-arch <a,b>    只保留这些架构的目标（例如 x86_64），Linux 默认为 x86_64,riscv64"
+    );
+    eprintln!("  --keep-env <a,b>     只保留这些环境的目标（例如 gnu,musl），默认保留所有环境");
+    eprintln!(
+        "  --keep-os <a,b>      只保留这些操作系统的目标（例如 linux,android），代替 --keep 的预设"
+    );
+    eprintln!("  --active-features <a,b>");
+    eprintln!("                       只保留这组 features 能启用的 target 配置");
+    eprintln!("  --cache <path>       增量缓存文件，跳过上次处理后未变化的文件");
+    eprintln!("  --record-ops <path>  把实际执行的修改按顺序写入文件（每行一个操作）");
+    eprintln!("  --only-if-present <table>");
+    eprintln!("                       只处理包含指定顶层表（例如 target）的文件（可重复）");
+    eprintln!("  --no-follow-symlinks 目录模式下跳过符号链接");
+    eprintln!("  --skip-invalid       目录模式下把有语法错误的文件报告为警告并跳过，不计为失败");
+    eprintln!("  --report-only-changed");
+    eprintln!("                       目录模式下只列出有改动的文件");
+    eprintln!(
+        "  --verbose            列出出现在被删除的 target 配置中、但仍在其他位置声明而保留的依赖"
+    );
+    eprintln!("  --quiet              不输出每个文件的修改摘要（删除的 target 配置、依赖和修改的 feature 数）");
+    eprintln!("  --require-content    处理后没有剩余依赖和 target 配置时报错");
+    eprintln!(
+        "  --validate           处理后校验输出仍是有效的 Cargo 清单，有悬空的 features 引用时报错"
+    );
+    eprintln!("  --max-removal-ratio <ratio>");
+    eprintln!("                       删除的依赖或 target 配置超过这一比例（0 到 1）时报错");
+    eprintln!("  --force              跳过 --max-removal-ratio 的检查");
+    eprintln!("  --prune-empty-features");
+    eprintln!("                       删除对清理后变为空的 feature 的引用");
+    eprintln!("  --remove-empty-features");
+    eprintln!(
+        "                       删除清理后变为空的 feature 本身（包含 --prune-empty-features）"
+    );
+    eprintln!("  --keep-dev-deps      被删除的 target 配置中有 dev-dependencies 时保留它们，");
+    eprintln!("                       只删除其中的 dependencies 和 build-dependencies");
+    eprintln!(
+        "  --hoist-deps         只在被删除的 target 配置中声明的非平台依赖移到顶层，不随之删除"
+    );
+    eprintln!("  --remove-unbuildable-targets");
+    eprintln!("                       [[bin]]、[[example]] 等的 required-features 引用了已删除的");
+    eprintln!("                       feature 或依赖时删除整个条目（默认只删除这些引用）");
+    eprintln!("  --prune-patches      删除 [patch] 和 [replace] 中指向已删除依赖的条目");
+    #[cfg(feature = "path-deps")]
+    {
+        eprintln!("  --resolve-path-deps  读取 path 依赖的清单，删除依赖方启用的平台特定 feature");
+    }
+    #[cfg(feature = "serde")]
+    {
+        eprintln!("  --json               照常写入输出，并在标准输出打印 JSON 格式的修改摘要");
+    }
+    eprintln!("  --conflict-resolution <keep-wins|remove-wins>");
+    eprintln!("                       依赖同时出现在保留和删除的 target 配置下时保留还是删除");
+    eprintln!("  --feature-cleaning <apply|report-only|skip>");
+    eprintln!("                       features 清理方式，report-only 只报告不修改");
+    eprintln!("  --comment-out        被删除的 target 配置和 feature 改为注释保留，便于审阅");
+    eprintln!();
+    eprintln!("子命令:");
+    eprintln!(
+        "  init-config          在当前目录生成 {} 模板",
+        CONFIG_FILE_NAME
+    );
+}
+
 fn main() {
+    // 删除决定的日志由 RUST_LOG 控制，例如 RUST_LOG=pre=debug
+    #[cfg(feature = "logging")]
+    env_logger::init();
+
     let args: Vec<String> = std::env::args().collect();
 
-    if args.len() < 2 {
-        eprintln!("用法: {} <input.toml> [output.toml]", args[0]);
-        eprintln!("  如果未指定output.toml，将覆盖原文件");
+    if args.get(1).map(String::as_str) == Some("init-config") {
+        init_config(&args[0], &args[2..]);
+        return;
+    }
+    if args.iter().skip(1).any(|arg| arg == "--version-json") {
+        print_version_json();
+        return;
+    }
+
+    let mut config_path = None;
+    let mut cache_path = None;
+    let mut only_if_present = Vec::new();
+    let mut record_ops = None;
+    let mut active_features = None;
+    let mut keep_arch = None;
+    let mut keep_env = None;
+    let mut keep_os = None;
+    let mut keep_targets: Option<Vec<String>> = None;
+    let mut header = false;
+    let mut aggressive = false;
+    let mut prune_empty_features = false;
+    let mut remove_empty_features = false;
+    let mut keep_dev_deps = false;
+    let mut hoist_deps = false;
+    let mut remove_unbuildable_targets = false;
+    let mut prune_patches = false;
+    let mut comment_out = false;
+    #[cfg(feature = "path-deps")]
+    let mut resolve_path_deps = false;
+    #[cfg(feature = "serde")]
+    let mut json = false;
+    #[cfg(not(feature = "serde"))]
+    let json = false;
+    let mut strip_deps = Vec::new();
+    let mut keep_deps = Vec::new();
+    let mut no_speculative = false;
+    let mut known_deps_files = Vec::new();
+    let mut feature_mode = None;
+    let mut conflict_resolution = None;
+    let mut keep = None;
+    let mut follow_symlinks = true;
+    let mut require_content = false;
+    let mut validate = false;
+    let mut max_removal_ratio = None;
+    let mut force = false;
+    let mut report_only_changed = false;
+    let mut skip_invalid = false;
+    let mut dump = false;
+    let mut verbose = false;
+    let mut quiet = false;
+    let mut dry_run = false;
+    let mut check = false;
+    let mut in_place = false;
+    let mut no_atomic = false;
+    let mut positional = Vec::new();
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--config" => config_path = Some(next_value(&mut iter, arg).clone()),
+            "--cache" => cache_path = Some(next_value(&mut iter, arg).clone()),
+            "--record-ops" => record_ops = Some(next_value(&mut iter, arg).clone()),
+            "--only-if-present" => only_if_present.push(next_value(&mut iter, arg).clone()),
+            "--header" => header = true,
+            "--dry-run" => dry_run = true,
+            "--check" => check = true,
+            "--in-place" => in_place = true,
+            "--no-atomic" => no_atomic = true,
+            "--aggressive" => aggressive = true,
+            "--prune-empty-features" => prune_empty_features = true,
+            "--remove-empty-features" => remove_empty_features = true,
+            "--keep-dev-deps" => keep_dev_deps = true,
+            "--hoist-deps" => hoist_deps = true,
+            "--remove-unbuildable-targets" => remove_unbuildable_targets = true,
+            "--prune-patches" => prune_patches = true,
+            "--comment-out" => comment_out = true,
+            #[cfg(feature = "path-deps")]
+            "--resolve-path-deps" => resolve_path_deps = true,
+            #[cfg(feature = "serde")]
+            "--json" => json = true,
+            "--strip-dep" => strip_deps.push(next_value(&mut iter, arg).clone()),
+            "--keep-dep" => keep_deps.push(next_value(&mut iter, arg).clone()),
+            "--no-speculative" => no_speculative = true,
+            // --platform-deps-file 与 --known-deps 相同，按出现顺序一起合并
+            "--known-deps" | "--platform-deps-file" => {
+                known_deps_files.push(next_value(&mut iter, arg).clone())
+            }
+            "--no-follow-symlinks" => follow_symlinks = false,
+            "--require-content" => require_content = true,
+            "--validate" => validate = true,
+            "--force" => force = true,
+            "--max-removal-ratio" => {
+                match next_value(&mut iter, arg)
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|ratio| (0.0..=1.0).contains(ratio))
+                {
+                    Some(ratio) => max_removal_ratio = Some(ratio),
+                    None => {
+                        eprintln!("✗ 错误: --max-removal-ratio 必须是 0 到 1 之间的数");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--report-only-changed" => report_only_changed = true,
+            "--skip-invalid" => skip_invalid = true,
+            // 调试用，不在用法中列出
+            "--dump-document" => dump = true,
+            "--verbose" => verbose = true,
+            "--quiet" => quiet = true,
+            "--active-features" => {
+                let list = next_value(&mut iter, arg);
+                active_features = Some(
+                    list.split(',')
+                        .map(str::trim)
+                        .filter(|f| !f.is_empty())
+                        .map(str::to_string)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            "--keep-target" => keep_targets
+                .get_or_insert_with(Vec::new)
+                .push(next_value(&mut iter, arg).clone()),
+            "--keep-arch" => {
+                let list = next_value(&mut iter, arg);
+                keep_arch = Some(
+                    list.split(',')
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .map(str::to_string)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            "--keep-env" => {
+                let list = next_value(&mut iter, arg);
+                keep_env = Some(
+                    list.split(',')
+                        .map(str::trim)
+                        .filter(|e| !e.is_empty())
+                        .map(str::to_string)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            "--keep-os" => {
+                let list = next_value(&mut iter, arg);
+                keep_os = Some(
+                    list.split(',')
+                        .map(str::trim)
+                        .filter(|o| !o.is_empty())
+                        .map(str::to_string)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            "--keep" | "--platform" => match next_value(&mut iter, arg).parse::<KeepTarget>() {
+                Ok(target) => keep = Some(target),
+                Err(e) => {
+                    eprintln!("✗ 错误: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--conflict-resolution" => {
+                match next_value(&mut iter, arg).parse::<ConflictResolution>() {
+                    Ok(resolution) => conflict_resolution = Some(resolution),
+                    Err(e) => {
+                        eprintln!("✗ 错误: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--feature-cleaning" => match next_value(&mut iter, arg).parse::<FeatureMode>() {
+                Ok(mode) => feature_mode = Some(mode),
+                Err(e) => {
+                    eprintln!("✗ 错误: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            _ if arg.starts_with("--") => {
+                eprintln!("✗ 错误: 未知选项 {}", arg);
+                print_usage(&args[0]);
+                std::process::exit(1);
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.is_empty() || (positional.len() > 2 && !in_place) {
+        print_usage(&args[0]);
+        std::process::exit(1);
+    }
+
+    let config = match config_path {
+        Some(path) => match Config::from_file(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("✗ 错误: {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+
+    let keep = keep.unwrap_or(config.keep);
+    let keep_triple = match keep.resolve() {
+        Ok(triple) => triple,
+        Err(e) => {
+            eprintln!("✗ 错误: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut keep_triples: Vec<String> = keep_triple.into_iter().collect();
+    for triple in keep_targets.unwrap_or_else(|| config.keep_target.clone()) {
+        if !keep_triples.contains(&triple) {
+            keep_triples.push(triple);
+        }
+    }
+    for triple in &keep_triples {
+        if cfg_expr::targets::get_builtin_target_by_triple(triple).is_none() {
+            eprintln!(
+                "⚠ 警告: 不认识的 triple {}，cfg(...) 条件无法对它求值，都会保留",
+                triple
+            );
+        }
+    }
+
+    let filter = TargetFilter {
+        active_features: active_features
+            .or_else(|| config.active_features.clone())
+            .map(|features| features.into_iter().collect()),
+        keep_triples,
+        preset: keep.preset(),
+        keep_arch: keep_arch.or_else(|| config.keep_arch.clone()),
+        keep_env: keep_env.or_else(|| config.keep_env.clone()),
+        keep_os: keep_os.or_else(|| config.keep_os.clone()),
+    };
+    // 拼错的架构名、环境名或系统名会让所有 target 配置都被删除
+    if filter.keep_triples.is_empty() && filter.preset_triples().is_empty() {
+        let mut options = Vec::new();
+        if let Some(arches) = &filter.keep_arch {
+            options.push(format!("--keep-arch {}", arches.join(",")));
+        }
+        if let Some(envs) = &filter.keep_env {
+            options.push(format!("--keep-env {}", envs.join(",")));
+        }
+        if let Some(oss) = &filter.keep_os {
+            options.push(format!("--keep-os {}", oss.join(",")));
+        }
+        eprintln!("✗ 错误: {} 没有匹配的目标", options.join(" "));
+        std::process::exit(1);
+    }
+
+    // 配置文件中的列表先合并，命令行指定的文件优先
+    let mut known_deps = KnownDeps::new();
+    for path in config.known_deps.iter().chain(&known_deps_files) {
+        if let Err(e) = known_deps.merge_file(path) {
+            eprintln!("✗ 错误: {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+
+    let feature_mode = feature_mode.unwrap_or(config.feature_cleaning);
+    let atomic_off = no_atomic || config.no_atomic;
+    let mut processor = Processor::new()
+        .filter(filter)
+        .feature_mode(feature_mode)
+        .conflict_resolution(conflict_resolution.unwrap_or(config.conflict_resolution))
+        .aggressive(aggressive || config.aggressive)
+        .prune_empty_features(prune_empty_features || config.prune_empty_features)
+        .remove_empty_features(remove_empty_features || config.remove_empty_features)
+        .strip_dev_dependencies(!(keep_dev_deps || config.keep_dev_deps))
+        .hoist_deps(hoist_deps || config.hoist_deps)
+        .remove_unbuildable_targets(remove_unbuildable_targets || config.remove_unbuildable_targets)
+        .prune_patches(prune_patches || config.prune_patches)
+        .strip_mode(if comment_out {
+            StripMode::Comment
+        } else {
+            config.strip_mode
+        })
+        .no_speculative(no_speculative || config.no_speculative)
+        .known_deps(known_deps)
+        .header(header || config.header)
+        .require_content(require_content || config.require_content)
+        .validate(validate || config.validate)
+        .atomic_write(!atomic_off);
+
+    #[cfg(feature = "path-deps")]
+    {
+        processor = processor.resolve_path_deps(resolve_path_deps);
+    }
+
+    // --force 跳过删除比例检查，包括配置文件中设置的上限
+    if let (false, Some(ratio)) = (force, max_removal_ratio.or(config.max_removal_ratio)) {
+        processor = processor.max_removal_ratio(ratio);
+    }
+
+    for name in config.keep_deps.iter().cloned().chain(keep_deps) {
+        processor = processor.keep_dep(name);
+    }
+    for name in strip_deps {
+        processor = processor.strip_dep(name);
+    }
+
+    // 配置指纹同时包含 .cargo/config.toml 的处理选项，任何影响输出的选项变化都会使缓存失效
+    let fingerprint = format!(
+        "{}|prune_build_targets={}",
+        processor.fingerprint(),
+        config.prune_build_targets
+    );
+    if dry_run && record_ops.is_some() {
+        eprintln!("✗ 错误: --dry-run 不能与 --record-ops 同时使用");
+        std::process::exit(1);
+    }
+    if check && (dry_run || record_ops.is_some()) {
+        eprintln!("✗ 错误: --check 不能与 --dry-run 或 --record-ops 同时使用");
+        std::process::exit(1);
+    }
+    if json && (dry_run || check) {
+        eprintln!("✗ 错误: --json 不能与 --dry-run 或 --check 同时使用");
+        std::process::exit(1);
+    }
+    // --dry-run 和 --check 不写入任何文件，也不读写缓存
+    let mut cache = match cache_path
+        .filter(|_| !dry_run && !check)
+        .map(|path| Cache::load(path, &fingerprint))
+    {
+        None => None,
+        Some(Ok(cache)) => Some(cache),
+        Some(Err(e)) => {
+            eprintln!("✗ 错误: 读取缓存失败: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let settings = RunSettings {
+        prune_build_targets: config.prune_build_targets,
+        feature_mode,
+        dump,
+        verbose,
+        quiet,
+        report_only_changed,
+        dry_run,
+        atomic: !atomic_off,
+        only_if_present,
+        record_ops,
+        json,
+        #[cfg(feature = "serde")]
+        json_report: Default::default(),
+    };
+
+    let input_path = &positional[0];
+
+    if in_place && positional.iter().any(|path| path == "-") {
+        eprintln!("✗ 错误: --in-place 不支持标准输入或标准输出");
         std::process::exit(1);
     }
 
-    let input_path = &args[1];
-    let output_path = if args.len() >= 3 {
-        &args[2]
+    if check {
+        if in_place {
+            eprintln!("✗ 错误: --check 不能与 --in-place 同时使用");
+            std::process::exit(1);
+        }
+        if positional.len() > 1 {
+            eprintln!("✗ 错误: --check 不能指定输出文件");
+            std::process::exit(1);
+        }
+        run_check(&mut processor, &settings, input_path, follow_symlinks);
+    }
+
+    // 管道模式：- 表示标准输入/输出，输入为 - 时默认输出到标准输出
+    let to_stdout = positional
+        .get(1)
+        .map_or(input_path == "-", |output| output == "-");
+    if input_path == "-" || to_stdout {
+        if settings.json {
+            eprintln!("✗ 错误: --json 不支持标准输入或标准输出");
+            std::process::exit(1);
+        }
+        let output_path = positional.get(1).map_or("-", String::as_str);
+        match process_stdio(&mut processor, &settings, input_path, output_path) {
+            // 处理结果可能写到标准输出，提示信息一律输出到 stderr
+            Ok(_) if dry_run => eprintln!(
+                "✓ 预览完成（未写入）: {}",
+                display_path(output_path, "<stdout>")
+            ),
+            Ok(_) => eprintln!("✓ 处理完成: {}", display_path(output_path, "<stdout>")),
+            Err(e) => {
+                eprintln!("✗ 错误: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // --dry-run 时 diff 占用标准输出，进度信息改为输出到 stderr；--json 时标准输出只有 JSON
+    let status = |line: String| {
+        if settings.dry_run {
+            eprintln!("{}", line);
+        } else if !settings.json {
+            println!("{}", line);
+        }
+    };
+    let done = if settings.dry_run {
+        "✓ 预览完成（未写入）"
+    } else {
+        "✓ 处理完成"
+    };
+
+    // 目录模式和 --in-place 的多文件模式：递归处理目录下所有 Cargo.toml 和指定的文件，原地覆盖
+    if Path::new(input_path).is_dir() || (in_place && positional.len() > 1) {
+        if positional.len() > 1 && !in_place {
+            eprintln!("✗ 错误: 目录模式下不能指定输出文件");
+            std::process::exit(1);
+        }
+        if settings.record_ops.is_some() {
+            eprintln!("✗ 错误: 目录模式和多文件模式下不支持 --record-ops");
+            std::process::exit(1);
+        }
+        let mut manifests = Vec::new();
+        for path in &positional {
+            if !Path::new(path).is_dir() {
+                manifests.push(PathBuf::from(path));
+                continue;
+            }
+            match pre::find_manifests(Path::new(path), follow_symlinks) {
+                Ok(found) => manifests.extend(found),
+                Err(e) => {
+                    eprintln!("✗ 错误: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        let mut failed = 0;
+        let mut invalid = 0;
+        let mut unchanged = 0;
+        #[cfg(feature = "serde")]
+        let mut json_entries = Vec::new();
+        for manifest in &manifests {
+            let path = manifest.to_string_lossy();
+            let result = process_one(&mut processor, &settings, cache.as_mut(), &path, &path);
+            #[cfg(feature = "serde")]
+            if let (true, Ok(outcome)) = (settings.json, &result) {
+                json_entries.push(json_entry(&settings, &path, outcome));
+            }
+            match result {
+                Ok(Outcome::Unchanged | Outcome::Cached | Outcome::Skipped)
+                    if report_only_changed =>
+                {
+                    unchanged += 1
+                }
+                Ok(Outcome::Cached) => status(format!("✓ 缓存命中，跳过: {}", path)),
+                Ok(Outcome::Skipped) => status(format!("✓ 缺少指定的表，跳过: {}", path)),
+                Ok(_) => status(format!("{}: {}", done, path)),
+                Err(e @ pre::ProcessError::ParseError(_)) if skip_invalid => {
+                    warn_invalid(&path, &e);
+                    invalid += 1;
+                }
+                Err(e) => {
+                    eprintln!("✗ {}: {}", path, e);
+                    failed += 1;
+                }
+            }
+        }
+        if invalid > 0 {
+            status(format!("跳过 {} 个有语法错误的文件", invalid));
+        }
+        if report_only_changed {
+            status(format!(
+                "共 {} 个文件，失败 {} 个，{} 个无需修改",
+                manifests.len(),
+                failed,
+                unchanged
+            ));
+        } else {
+            status(format!("共 {} 个文件，失败 {} 个", manifests.len(), failed));
+        }
+        #[cfg(feature = "serde")]
+        if settings.json {
+            print_json(&json_entries);
+        }
+        save_cache(cache.as_ref());
+        if failed > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let output_path = positional.get(1).unwrap_or(input_path);
+    let result = process_one(
+        &mut processor,
+        &settings,
+        cache.as_mut(),
+        input_path,
+        output_path,
+    );
+    #[cfg(feature = "serde")]
+    if let (true, Ok(outcome)) = (settings.json, &result) {
+        print_json(&json_entry(&settings, output_path, outcome));
+    }
+    match result {
+        Ok(Outcome::Cached) => status(format!("✓ 缓存命中，跳过: {}", input_path)),
+        Ok(Outcome::Skipped) => status(format!("✓ 缺少指定的表，跳过: {}", input_path)),
+        Ok(_) => status(format!("{}: {}", done, output_path)),
+        Err(e) => {
+            eprintln!("✗ 错误: {}", e);
+            std::process::exit(1);
+        }
+    }
+    save_cache(cache.as_ref());
+}
+
+/// 单个文件的处理结果
+enum Outcome {
+    /// 内容有改动
+    Changed,
+    /// 处理后内容不变
+    Unchanged,
+    /// 缓存命中，未重新处理
+    Cached,
+    /// 不包含 `--only-if-present` 指定的表，未处理
+    Skipped,
+}
+
+/// 对每个文件都相同的命令行设置（处理选项本身在 [`Processor`] 中）
+struct RunSettings {
+    prune_build_targets: bool,
+    feature_mode: FeatureMode,
+    dump: bool,
+    /// 列出出现在被删除的 target 配置中但仍被保留的依赖
+    verbose: bool,
+    /// 不输出修改摘要
+    quiet: bool,
+    /// 只列出有改动的文件，无需修改的文件不输出修改摘要
+    report_only_changed: bool,
+    /// 只输出 diff，不写入任何文件
+    dry_run: bool,
+    /// 经过临时文件原子地写入输出文件
+    atomic: bool,
+    /// 只处理至少包含其中一个顶层表的文件，为空时不限制
+    only_if_present: Vec<String>,
+    /// 记录实际执行的修改的文件
+    record_ops: Option<String>,
+    /// 在标准输出打印 JSON 摘要，不输出其他进度信息
+    json: bool,
+    /// `--json` 时最近一次处理的计数和报告，由 [`json_entry`] 取走
+    #[cfg(feature = "serde")]
+    json_report: std::cell::RefCell<Option<serde_json::Value>>,
+}
+
+impl From<bool> for Outcome {
+    fn from(changed: bool) -> Self {
+        if changed {
+            Outcome::Changed
+        } else {
+            Outcome::Unchanged
+        }
+    }
+}
+
+/// 处理单个文件：`.cargo/config.toml` 或 Cargo.toml
+///
+/// 指定了 `--only-if-present` 时，不包含这些表的文件直接跳过，不会写入；
+/// 指定了缓存时，输入内容与上次处理后一致、输出到同一个文件且该文件存在时直接跳过；
+/// 处理完成后记录输入文件当前的内容（原地处理时即处理结果）
+fn process_one(
+    processor: &mut Processor,
+    settings: &RunSettings,
+    cache: Option<&mut Cache>,
+    input_path: &str,
+    output_path: &str,
+) -> Result<Outcome, pre::ProcessError> {
+    let input = Path::new(input_path);
+    let content = read_input(input_path)?;
+    if !settings.only_if_present.is_empty()
+        && !settings
+            .only_if_present
+            .iter()
+            .any(|table| pre::has_top_level_table(&content, table))
+    {
+        return Ok(Outcome::Skipped);
+    }
+
+    let Some(cache) = cache else {
+        return process_uncached(processor, settings, input_path, output_path).map(Outcome::from);
+    };
+
+    let output = Path::new(output_path);
+    if cache.is_fresh_for(input, output, &content) && output.exists() {
+        return Ok(Outcome::Cached);
+    }
+    let changed = process_uncached(processor, settings, input_path, output_path)?;
+    cache.record_for(input, output, &read_input(input_path)?);
+    Ok(changed.into())
+}
+
+/// 处理单个文件，返回内容是否有改动
+fn process_uncached(
+    processor: &mut Processor,
+    settings: &RunSettings,
+    input_path: &str,
+    output_path: &str,
+) -> Result<bool, pre::ProcessError> {
+    if settings.dry_run {
+        return dry_run(processor, settings, input_path, output_path);
+    }
+    if is_cargo_config(input_path) {
+        let content = read_input(input_path)?;
+        let (output, report) = pre::process_cargo_config_string_with_filter(
+            &content,
+            settings.prune_build_targets,
+            processor.target_filter(),
+        )?;
+        pre::write_output_file(output_path, &output, settings.atomic)?;
+        for warning in &report.warnings {
+            eprintln!("⚠ 警告: {}", warning);
+        }
+        #[cfg(feature = "serde")]
+        record_json(settings, &report, [report.removed_targets.len(), 0, 0])?;
+        return Ok(report.changed);
+    }
+
+    if settings.dump {
+        dump_content("处理前", input_path, &read_input(input_path)?)?;
+    }
+    let report = processor.process_file(input_path, output_path)?;
+    if settings.dump {
+        dump_content(
+            "处理后",
+            output_path,
+            &std::fs::read_to_string(output_path)?,
+        )?;
+    }
+    print_report(settings, &report, settings.json)?;
+    #[cfg(feature = "serde")]
+    record_json(
+        settings,
+        &report,
+        [
+            report.removed_targets.len(),
+            report.removed_deps.len(),
+            report.modified_features.len(),
+        ],
+    )?;
+    Ok(report.changed)
+}
+
+/// `--json` 时记录处理报告，`counts` 为删除的 target 配置数、依赖数和修改的 feature 数
+#[cfg(feature = "serde")]
+fn record_json(
+    settings: &RunSettings,
+    report: &impl serde::Serialize,
+    counts: [usize; 3],
+) -> Result<(), pre::ProcessError> {
+    if !settings.json {
+        return Ok(());
+    }
+    let report = serde_json::to_value(report).map_err(std::io::Error::from)?;
+    let [removed_targets, removed_dependencies, modified_features] = counts;
+    settings.json_report.replace(Some(serde_json::json!({
+        "counts": {
+            "removed_targets": removed_targets,
+            "removed_dependencies": removed_dependencies,
+            "modified_features": modified_features,
+        },
+        "report": report,
+    })));
+    Ok(())
+}
+
+/// `--json` 输出格式的版本，字段有不兼容的变化（删除、改名、含义改变）或报告中增加字段时加一
+#[cfg(feature = "serde")]
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// 一个文件的 JSON 摘要：格式版本、路径、结果（changed、unchanged、cached、skipped），
+/// 实际处理过的文件还有计数和完整的报告
+#[cfg(feature = "serde")]
+fn json_entry(settings: &RunSettings, path: &str, outcome: &Outcome) -> serde_json::Value {
+    let status = match outcome {
+        Outcome::Changed => "changed",
+        Outcome::Unchanged => "unchanged",
+        Outcome::Cached => "cached",
+        Outcome::Skipped => "skipped",
+    };
+    let mut entry = serde_json::Map::new();
+    entry.insert("schema_version".to_string(), JSON_SCHEMA_VERSION.into());
+    entry.insert("path".to_string(), path.into());
+    entry.insert("status".to_string(), status.into());
+    if let Some(serde_json::Value::Object(processed)) = settings.json_report.take() {
+        entry.extend(processed);
+    }
+    serde_json::Value::Object(entry)
+}
+
+#[cfg(feature = "serde")]
+fn print_json(value: &impl serde::Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("✗ 错误: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `-` 作为输入时从标准输入读取，作为输出时写到标准输出；不使用缓存
+fn process_stdio(
+    processor: &mut Processor,
+    settings: &RunSettings,
+    input_path: &str,
+    output_path: &str,
+) -> Result<bool, pre::ProcessError> {
+    let content = read_input(input_path)?;
+
+    let (output, changed) =
+        process_content(processor, settings, &content, input_path, output_path)?;
+    if settings.dry_run {
+        print_diff(&content, &output, input_path, output_path);
+    } else if output_path == "-" {
+        print!("{}", output);
+    } else {
+        pre::write_output_file(output_path, &output, settings.atomic)?;
+    }
+    Ok(changed)
+}
+
+/// `--check`：在内存中处理文件（目录模式下为目录中的所有清单），不写入任何文件
+///
+/// 有文件需要修改时列出将被删除的内容并以状态 1 退出，全部无需修改时以状态 0 退出
+fn run_check(
+    processor: &mut Processor,
+    settings: &RunSettings,
+    input_path: &str,
+    follow_symlinks: bool,
+) -> ! {
+    let is_dir = Path::new(input_path).is_dir();
+    let paths: Vec<String> = if is_dir {
+        match pre::find_manifests(Path::new(input_path), follow_symlinks) {
+            Ok(manifests) => manifests
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect(),
+            Err(e) => {
+                eprintln!("✗ 错误: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        vec![input_path.to_string()]
+    };
+
+    let mut dirty = 0;
+    let mut failed = 0;
+    for path in &paths {
+        let display = display_path(path, "<stdin>");
+        match check_one(processor, settings, path) {
+            Ok(removals) if removals.is_empty() => println!("✓ 无需修改: {}", display),
+            Ok(removals) => {
+                println!("✗ 需要清理: {}", display);
+                for line in removals {
+                    println!("  {}", line);
+                }
+                dirty += 1;
+            }
+            Err(e) => {
+                eprintln!("✗ {}: {}", display, e);
+                failed += 1;
+            }
+        }
+    }
+    if is_dir {
+        println!(
+            "共 {} 个文件，{} 个需要清理，失败 {} 个",
+            paths.len(),
+            dirty,
+            failed
+        );
+    }
+    std::process::exit(if dirty > 0 || failed > 0 { 1 } else { 0 });
+}
+
+/// 检查单个文件，返回将被删除的内容，每项一行；无需修改时为空
+fn check_one(
+    processor: &mut Processor,
+    settings: &RunSettings,
+    input_path: &str,
+) -> Result<Vec<String>, pre::ProcessError> {
+    let content = read_input(input_path)?;
+    if !settings.only_if_present.is_empty()
+        && !settings
+            .only_if_present
+            .iter()
+            .any(|table| pre::has_top_level_table(&content, table))
+    {
+        return Ok(Vec::new());
+    }
+
+    let mut removals = Vec::new();
+    if is_cargo_config(input_path) {
+        let (_, report) = pre::process_cargo_config_string_with_filter(
+            &content,
+            settings.prune_build_targets,
+            processor.target_filter(),
+        )?;
+        for warning in &report.warnings {
+            eprintln!("⚠ 警告: {}", warning);
+        }
+        if !report.changed {
+            return Ok(removals);
+        }
+        for target in &report.removed_targets {
+            removals.push(format!("删除 [target.{}]", target));
+        }
+        if !report.removed_build_targets.is_empty() {
+            removals.push(format!(
+                "删除 build.target 中的 {}",
+                report.removed_build_targets.join(", ")
+            ));
+        }
+        return Ok(removals);
+    }
+
+    let (_, report) = if input_path == "-" {
+        processor.run(&content)?
+    } else {
+        processor.run_with_path(&content, input_path)?
+    };
+    for warning in &report.warnings {
+        eprintln!("⚠ 警告: {}", warning);
+    }
+    if !report.changed {
+        return Ok(removals);
+    }
+    for target in &report.removed_targets {
+        removals.push(format!("删除 target 配置 {}", target));
+    }
+    let deps = &report.removed_deps;
+    for (kind, names) in [
+        ("dependencies", &deps.dependencies),
+        ("dev-dependencies", &deps.dev_dependencies),
+        ("build-dependencies", &deps.build_dependencies),
+    ] {
+        if !names.is_empty() {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            removals.push(format!("删除 {}: {}", kind, names.join(", ")));
+        }
+    }
+    if settings.feature_mode == FeatureMode::Apply {
+        for (feature, items) in &report.modified_features {
+            removals.push(format!("features.{} 删除: {}", feature, items.join(", ")));
+        }
+    }
+    // 其他修改（例如 --header 添加的生成标记）没有单独列出
+    if removals.is_empty() {
+        removals.push("内容有改动".to_string());
+    }
+    Ok(removals)
+}
+
+/// `--dry-run`：处理文件但不写入，输出原内容与处理结果的 unified diff
+fn dry_run(
+    processor: &mut Processor,
+    settings: &RunSettings,
+    input_path: &str,
+    output_path: &str,
+) -> Result<bool, pre::ProcessError> {
+    let content = read_input(input_path)?;
+    let (output, changed) =
+        process_content(processor, settings, &content, input_path, output_path)?;
+    print_diff(&content, &output, input_path, output_path);
+    Ok(changed)
+}
+
+fn print_diff(content: &str, output: &str, input_path: &str, output_path: &str) {
+    print!(
+        "{}",
+        pre::unified_diff(
+            content,
+            output,
+            display_path(input_path, "<stdin>"),
+            display_path(output_path, "<stdout>")
+        )
+    );
+}
+
+/// 在内存中处理已读取的内容，返回处理结果和是否有改动
+///
+/// 不包含 `--only-if-present` 指定的表时原样返回
+fn process_content(
+    processor: &mut Processor,
+    settings: &RunSettings,
+    content: &str,
+    input_path: &str,
+    output_path: &str,
+) -> Result<(String, bool), pre::ProcessError> {
+    let skipped = !settings.only_if_present.is_empty()
+        && !settings
+            .only_if_present
+            .iter()
+            .any(|table| pre::has_top_level_table(content, table));
+    let result = if skipped {
+        (content.to_string(), false)
+    } else if is_cargo_config(input_path) {
+        let (output, report) = pre::process_cargo_config_string_with_filter(
+            content,
+            settings.prune_build_targets,
+            processor.target_filter(),
+        )?;
+        for warning in &report.warnings {
+            eprintln!("⚠ 警告: {}", warning);
+        }
+        (output, report.changed)
+    } else {
+        if settings.dump {
+            dump_content("处理前", display_path(input_path, "<stdin>"), content)?;
+        }
+        let (output, report) = if input_path == "-" {
+            processor.run(content)?
+        } else {
+            processor.run_with_path(content, input_path)?
+        };
+        if settings.dump {
+            dump_content("处理后", display_path(output_path, "<stdout>"), &output)?;
+        }
+        // --dry-run 时 diff 占用标准输出，输出到标准输出时标准输出只有处理结果
+        print_report(settings, &report, settings.dry_run || output_path == "-")?;
+        (output, report.changed)
+    };
+    Ok(result)
+}
+
+/// 输出处理报告中的警告、--record-ops、修改摘要、report-only 模式下将删除的条目和 --verbose 的核对信息
+///
+/// `to_stderr` 为 true 时（处理结果写到标准输出）摘要、report-only 和 --verbose 的内容也输出到 stderr；
+/// `--dry-run` 时 diff 占用标准输出，这些内容总是输出到 stderr
+fn print_report(
+    settings: &RunSettings,
+    report: &pre::ProcessReport,
+    to_stderr: bool,
+) -> Result<(), pre::ProcessError> {
+    for warning in &report.warnings {
+        eprintln!("⚠ 警告: {}", warning);
+    }
+    if let Some(path) = &settings.record_ops {
+        std::fs::write(path, pre::serialize_operations(&report.operations))?;
+    }
+    let mut lines = Vec::new();
+    // --json 时标准输出只有 JSON，摘要中的计数已包含在其中
+    if !settings.quiet && !settings.json && (report.changed || !settings.report_only_changed) {
+        // report-only 模式下 features 没有被修改，计数的是将被修改的 feature
+        let modified = if settings.feature_mode == FeatureMode::ReportOnly {
+            "将修改"
+        } else {
+            "修改了"
+        };
+        lines.push(format!(
+            "  删除了 {} 个 target 配置、{} 个依赖，{} {} 个 feature",
+            report.targets_before.saturating_sub(report.targets_after),
+            report.deps_removed_count,
+            modified,
+            report.modified_features.len()
+        ));
+    }
+    if settings.feature_mode == FeatureMode::ReportOnly {
+        for (feature, items) in &report.modified_features {
+            lines.push(format!(
+                "  features.{} 将删除: {}",
+                feature,
+                items.join(", ")
+            ));
+        }
+    }
+    if settings.verbose && !report.ambiguous_deps.is_empty() {
+        let deps: Vec<&str> = report.ambiguous_deps.iter().map(String::as_str).collect();
+        lines.push(format!(
+            "  出现在被删除的 target 配置中但仍在其他位置声明，已保留: {}",
+            deps.join(", ")
+        ));
+    }
+    for line in lines {
+        if to_stderr || settings.dry_run {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// 读取输入，`-` 表示标准输入；与库相同，无效的 UTF-8 报告为带位置的 [`pre::ProcessError::InvalidUtf8`]
+fn read_input(path: &str) -> Result<String, pre::ProcessError> {
+    let bytes = if path == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        bytes
     } else {
-        input_path
+        std::fs::read(path)?
     };
+    pre::decode_manifest(&bytes).map(str::to_string)
+}
+
+/// 提示信息中的路径，`-` 显示为 `stdio`（`<stdin>` 或 `<stdout>`）
+fn display_path<'a>(path: &'a str, stdio: &'a str) -> &'a str {
+    if path == "-" {
+        stdio
+    } else {
+        path
+    }
+}
+
+/// `--dump-document`：把解析后的文档结构输出到 stderr
+fn dump_content(stage: &str, path: &str, content: &str) -> Result<(), pre::ProcessError> {
+    let doc = content.parse::<DocumentMut>()?;
+    eprintln!("[debug] ===== {} {} 文档结构 =====", stage, path);
+    eprint!("{}", pre::dump_document(&doc));
+    Ok(())
+}
+
+/// `--skip-invalid`：把语法错误报告为一行警告，带上行列号
+fn warn_invalid(path: &str, error: &pre::ProcessError) {
+    let location = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| error.parse_location(&content));
+    let message = match error {
+        pre::ProcessError::ParseError(e) => e.message().trim().to_string(),
+        _ => error.to_string(),
+    };
+    match location {
+        Some((line, column)) => eprintln!(
+            "⚠ 警告: {}:{}:{}: TOML 语法错误，已跳过: {}",
+            path, line, column, message
+        ),
+        None => eprintln!("⚠ 警告: {}: TOML 语法错误，已跳过: {}", path, message),
+    }
+}
+
+/// 写回缓存文件，失败时只给出警告
+fn save_cache(cache: Option<&Cache>) {
+    if let Some(Err(e)) = cache.map(Cache::save) {
+        eprintln!("⚠ 警告: 写入缓存失败: {}", e);
+    }
+}
+
+/// 读取选项的参数值，缺少参数时退出
+fn next_value<'a>(iter: &mut std::slice::Iter<'a, String>, flag: &str) -> &'a String {
+    match iter.next() {
+        Some(value) => value,
+        None => {
+            eprintln!("✗ 错误: {} 需要一个参数", flag);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `.cargo/config.toml`（或旧式的 `.cargo/config`）
+fn is_cargo_config(path: &str) -> bool {
+    let path = Path::new(path);
+    let in_cargo_dir = path
+        .parent()
+        .and_then(|p| p.file_name())
+        .is_some_and(|name| name == ".cargo");
+    let is_config_name = path
+        .file_name()
+        .is_some_and(|name| name == "config.toml" || name == "config");
+    in_cargo_dir && is_config_name
+}
+
+fn init_config(program: &str, args: &[String]) {
+    let mut force = false;
+    for arg in args {
+        match arg.as_str() {
+            "--force" => force = true,
+            _ => {
+                eprintln!("✗ 错误: 未知参数 {}", arg);
+                print_usage(program);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if Path::new(CONFIG_FILE_NAME).exists() && !force {
+        eprintln!("✗ 错误: {} 已存在，使用 --force 覆盖", CONFIG_FILE_NAME);
+        std::process::exit(1);
+    }
 
-    match pre::process_toml_file(input_path, output_path) {
-        Ok(()) => println!("✓ 处理完成: {}", output_path),
+    match std::fs::write(CONFIG_FILE_NAME, Config::template()) {
+        Ok(()) => println!("✓ 已生成配置文件: {}", CONFIG_FILE_NAME),
         Err(e) => {
             eprintln!("✗ 错误: {}", e);
             std::process::exit(1);
@@ -0,0 +1,344 @@
+use crate::ProcessError;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+/// 处理过程中实际执行的一步修改，按执行顺序记录在 [`crate::ProcessReport::operations`] 中
+///
+/// 可以用 [`serialize_operations`] 保存，再用 [`replay`] 应用到另一份文档上
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Operation {
+    /// 删除 `[target]` 下的一项，或顶层的 `target.xxx` 键
+    RemoveTarget(String),
+    /// 把 `[target]` 下一项中的依赖复制到顶层依赖表，在删除这一项之前执行
+    HoistDep {
+        target: String,
+        kind: String,
+        name: String,
+    },
+    /// 从依赖表中删除一个依赖；`target` 为 None 时是顶层依赖表
+    RemoveDep {
+        target: Option<String>,
+        kind: String,
+        name: String,
+    },
+    /// 删除 `[workspace.target]` 下的一项
+    RemoveWorkspaceTarget(String),
+    /// 从 `[workspace.dependencies]` 中删除一个依赖
+    RemoveWorkspaceDep(String),
+    /// 从 feature 中删除一个条目
+    StripFeatureItem { feature: String, item: String },
+    /// 删除 `[features]` 中的一个 feature
+    RemoveFeature(String),
+    /// 从依赖声明的 `features = [...]` 中删除一项；`target` 为 None 时是顶层依赖表
+    StripDepFeature {
+        target: Option<String>,
+        kind: String,
+        name: String,
+        feature: String,
+    },
+    /// 从 `[[bin]]`、`[[example]]` 等段的第 `index` 个条目的 `required-features` 中删除一项
+    StripRequiredFeature {
+        section: String,
+        index: usize,
+        item: String,
+    },
+    /// 删除 `[[bin]]`、`[[example]]` 等段的第 `index` 个条目
+    RemoveCargoTarget { section: String, index: usize },
+    /// 从 `[patch.<source>]` 中删除一个条目
+    RemovePatch { source: String, name: String },
+    /// 从 `[replace]` 中删除一个条目
+    RemoveReplace(String),
+}
+
+/// 每行一个操作，字段之间用制表符分隔（cfg 表达式中可能有空格）
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::RemoveTarget(key) => write!(f, "remove-target\t{}", key),
+            Operation::RemoveDep { target, kind, name } => {
+                write!(f, "remove-dep\t{}\t{}", kind, name)?;
+                if let Some(target) = target {
+                    write!(f, "\t{}", target)?;
+                }
+                Ok(())
+            }
+            Operation::HoistDep { target, kind, name } => {
+                write!(f, "hoist-dep\t{}\t{}\t{}", kind, name, target)
+            }
+            Operation::RemoveWorkspaceTarget(key) => write!(f, "remove-workspace-target\t{}", key),
+            Operation::RemoveWorkspaceDep(name) => write!(f, "remove-workspace-dep\t{}", name),
+            Operation::StripFeatureItem { feature, item } => {
+                write!(f, "strip-feature-item\t{}\t{}", feature, item)
+            }
+            Operation::RemoveFeature(name) => write!(f, "remove-feature\t{}", name),
+            Operation::StripDepFeature {
+                target,
+                kind,
+                name,
+                feature,
+            } => {
+                write!(f, "strip-dep-feature\t{}\t{}\t{}", kind, name, feature)?;
+                if let Some(target) = target {
+                    write!(f, "\t{}", target)?;
+                }
+                Ok(())
+            }
+            Operation::StripRequiredFeature {
+                section,
+                index,
+                item,
+            } => write!(
+                f,
+                "strip-required-feature\t{}\t{}\t{}",
+                section, index, item
+            ),
+            Operation::RemoveCargoTarget { section, index } => {
+                write!(f, "remove-cargo-target\t{}\t{}", section, index)
+            }
+            Operation::RemovePatch { source, name } => {
+                write!(f, "remove-patch\t{}\t{}", source, name)
+            }
+            Operation::RemoveReplace(spec) => write!(f, "remove-replace\t{}", spec),
+        }
+    }
+}
+
+impl std::str::FromStr for Operation {
+    type Err = ProcessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split('\t').collect();
+        match fields.as_slice() {
+            ["remove-target", key] => Ok(Operation::RemoveTarget(key.to_string())),
+            ["remove-dep", kind, name, rest @ ..] if rest.len() <= 1 => Ok(Operation::RemoveDep {
+                target: rest.first().map(|t| t.to_string()),
+                kind: kind.to_string(),
+                name: name.to_string(),
+            }),
+            ["hoist-dep", kind, name, target] => Ok(Operation::HoistDep {
+                target: target.to_string(),
+                kind: kind.to_string(),
+                name: name.to_string(),
+            }),
+            ["remove-workspace-target", key] => {
+                Ok(Operation::RemoveWorkspaceTarget(key.to_string()))
+            }
+            ["remove-workspace-dep", name] => Ok(Operation::RemoveWorkspaceDep(name.to_string())),
+            ["strip-feature-item", feature, item] => Ok(Operation::StripFeatureItem {
+                feature: feature.to_string(),
+                item: item.to_string(),
+            }),
+            ["remove-feature", name] => Ok(Operation::RemoveFeature(name.to_string())),
+            ["strip-dep-feature", kind, name, feature, rest @ ..] if rest.len() <= 1 => {
+                Ok(Operation::StripDepFeature {
+                    target: rest.first().map(|t| t.to_string()),
+                    kind: kind.to_string(),
+                    name: name.to_string(),
+                    feature: feature.to_string(),
+                })
+            }
+            ["strip-required-feature", section, index, item] => {
+                Ok(Operation::StripRequiredFeature {
+                    section: section.to_string(),
+                    index: parse_index(index, s)?,
+                    item: item.to_string(),
+                })
+            }
+            ["remove-cargo-target", section, index] => Ok(Operation::RemoveCargoTarget {
+                section: section.to_string(),
+                index: parse_index(index, s)?,
+            }),
+            ["remove-patch", source, name] => Ok(Operation::RemovePatch {
+                source: source.to_string(),
+                name: name.to_string(),
+            }),
+            ["remove-replace", spec] => Ok(Operation::RemoveReplace(spec.to_string())),
+            _ => Err(ProcessError::ConfigError(format!("无法识别的操作: {}", s))),
+        }
+    }
+}
+
+fn parse_index(index: &str, line: &str) -> Result<usize, ProcessError> {
+    index
+        .parse()
+        .map_err(|_| ProcessError::ConfigError(format!("操作中的下标无效: {}", line)))
+}
+
+/// 把操作列表序列化为文本，每行一个
+pub fn serialize_operations(ops: &[Operation]) -> String {
+    ops.iter().map(|op| format!("{}\n", op)).collect()
+}
+
+/// 解析 [`serialize_operations`] 的输出，忽略空行
+pub fn parse_operations(text: &str) -> Result<Vec<Operation>, ProcessError> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// 把记录的操作按顺序应用到文档上
+///
+/// 与原始处理一样，删除后变为空的依赖表、target 配置和 `[target]` 表会一并删除；
+/// 文档中不存在的目标会被忽略
+pub fn replay(doc: &mut DocumentMut, ops: &[Operation]) {
+    for op in ops {
+        match op {
+            Operation::RemoveTarget(key) => {
+                let in_target_table = doc
+                    .get_mut("target")
+                    .and_then(Item::as_table_like_mut)
+                    .and_then(|targets| targets.remove(key))
+                    .is_some();
+                if !in_target_table {
+                    doc.remove(key);
+                }
+                remove_if_empty(doc.as_table_mut(), "target");
+            }
+            Operation::RemoveDep { target, kind, name } => match target {
+                None => remove_dep(doc.as_table_mut(), kind, name),
+                Some(target) => {
+                    if let Some(targets) = doc.get_mut("target").and_then(Item::as_table_like_mut) {
+                        if let Some(spec) =
+                            targets.get_mut(target).and_then(Item::as_table_like_mut)
+                        {
+                            remove_dep(spec, kind, name);
+                        }
+                        remove_if_empty(targets, target);
+                    }
+                    remove_if_empty(doc.as_table_mut(), "target");
+                }
+            },
+            Operation::HoistDep { target, kind, name } => {
+                let item = doc
+                    .get("target")
+                    .and_then(|targets| targets.get(target))
+                    .and_then(|spec| spec.get(kind))
+                    .and_then(|deps| deps.get(name))
+                    .cloned();
+                if let Some(item) = item {
+                    crate::insert_top_level_dep(doc, kind, name, item);
+                }
+            }
+            Operation::RemoveWorkspaceTarget(key) => {
+                if let Some(workspace) = doc.get_mut("workspace").and_then(Item::as_table_like_mut)
+                {
+                    if let Some(targets) = workspace
+                        .get_mut("target")
+                        .and_then(Item::as_table_like_mut)
+                    {
+                        targets.remove(key);
+                    }
+                    remove_if_empty(workspace, "target");
+                }
+            }
+            Operation::RemoveWorkspaceDep(name) => {
+                if let Some(workspace) = doc.get_mut("workspace").and_then(Item::as_table_like_mut)
+                {
+                    remove_dep(workspace, "dependencies", name);
+                }
+            }
+            Operation::StripDepFeature {
+                target,
+                kind,
+                name,
+                feature,
+            } => {
+                let table = match target {
+                    None => Some(doc.as_table_mut() as &mut dyn TableLike),
+                    Some(target) => doc
+                        .get_mut("target")
+                        .and_then(Item::as_table_like_mut)
+                        .and_then(|targets| targets.get_mut(target))
+                        .and_then(Item::as_table_like_mut),
+                };
+                if let Some(features) = table
+                    .and_then(|t| t.get_mut(kind))
+                    .and_then(Item::as_table_like_mut)
+                    .and_then(|deps| deps.get_mut(name))
+                    .and_then(Item::as_table_like_mut)
+                    .and_then(|dep| dep.get_mut("features"))
+                    .and_then(Item::as_array_mut)
+                {
+                    features.retain(|v| v.as_str() != Some(feature.as_str()));
+                    if let Some(first) = features.get_mut(0) {
+                        first.decor_mut().set_prefix("");
+                    }
+                }
+            }
+            Operation::StripFeatureItem { feature, item } => {
+                if let Some(features) = doc.get_mut("features").and_then(Item::as_table_like_mut) {
+                    if let Some(array) = features.get_mut(feature).and_then(Item::as_array_mut) {
+                        crate::retain_feature_items(array, |v| v != item);
+                    }
+                }
+            }
+            Operation::RemoveFeature(name) => {
+                if let Some(features) = doc.get_mut("features").and_then(Item::as_table_like_mut) {
+                    features.remove(name);
+                }
+                remove_if_empty(doc.as_table_mut(), "features");
+            }
+            Operation::StripRequiredFeature {
+                section,
+                index,
+                item,
+            } => {
+                if let Some(target) = doc
+                    .get_mut(section)
+                    .and_then(Item::as_array_of_tables_mut)
+                    .and_then(|targets| targets.get_mut(*index))
+                {
+                    if let Some(array) = target
+                        .get_mut("required-features")
+                        .and_then(Item::as_array_mut)
+                    {
+                        crate::retain_feature_items(array, |v| v != item);
+                        if array.is_empty() {
+                            target.remove("required-features");
+                        }
+                    }
+                }
+            }
+            Operation::RemoveCargoTarget { section, index } => {
+                if let Some(targets) = doc.get_mut(section).and_then(Item::as_array_of_tables_mut) {
+                    if *index < targets.len() {
+                        targets.remove(*index);
+                    }
+                    if targets.is_empty() {
+                        doc.remove(section);
+                    }
+                }
+            }
+            Operation::RemovePatch { source, name } => {
+                if let Some(patch) = doc.get_mut("patch").and_then(Item::as_table_like_mut) {
+                    remove_dep(patch, source, name);
+                }
+                remove_if_empty(doc.as_table_mut(), "patch");
+            }
+            Operation::RemoveReplace(spec) => {
+                if let Some(replace) = doc.get_mut("replace").and_then(Item::as_table_like_mut) {
+                    replace.remove(spec);
+                }
+                remove_if_empty(doc.as_table_mut(), "replace");
+            }
+        }
+    }
+}
+
+fn remove_dep(table: &mut dyn TableLike, kind: &str, name: &str) {
+    if let Some(deps) = table.get_mut(kind).and_then(Item::as_table_like_mut) {
+        deps.remove(name);
+    }
+    remove_if_empty(table, kind);
+}
+
+fn remove_if_empty(table: &mut dyn TableLike, key: &str) {
+    if table
+        .get(key)
+        .and_then(Item::as_table_like)
+        .is_some_and(|t| t.is_empty())
+    {
+        table.remove(key);
+    }
+}
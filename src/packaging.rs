@@ -0,0 +1,137 @@
+use crate::cfg::{self, CfgExpr};
+use crate::TargetFilter;
+use std::collections::BTreeSet;
+use toml_edit::{DocumentMut, Item, Value};
+
+/// 识别的打包工具元数据：`[package.metadata.<name>]`（cargo-deb、cargo-generate-rpm）
+const PACKAGING_SECTIONS: &[&str] = &["deb", "generate-rpm"];
+
+/// 检查打包工具元数据中是否提到了被删除的平台
+///
+/// 元数据是自由格式，只在字符串中查找被删除的 triple 和平台名（例如 `windows`、`aarch64`），
+/// 每处可能过时的内容返回一条提示信息。元数据本身不做修改
+pub(crate) fn check_stale_packaging_metadata(
+    doc: &DocumentMut,
+    removed_targets: &[String],
+    filter: &TargetFilter,
+) -> Vec<String> {
+    let markers: BTreeSet<String> = removed_targets
+        .iter()
+        .flat_map(|spec| removed_platform_names(spec, filter))
+        .collect();
+    if markers.is_empty() {
+        return Vec::new();
+    }
+
+    let Some(metadata) = doc
+        .get("package")
+        .and_then(|p| p.get("metadata"))
+        .and_then(Item::as_table_like)
+    else {
+        return Vec::new();
+    };
+
+    let mut messages = Vec::new();
+    for &section in PACKAGING_SECTIONS {
+        let Some(item) = metadata.get(section) else {
+            continue;
+        };
+        let mut strings = Vec::new();
+        collect_strings(item, &mut strings);
+
+        let mut found = BTreeSet::new();
+        for s in &strings {
+            for marker in &markers {
+                if mentions(s, marker) {
+                    found.insert(marker.as_str());
+                }
+            }
+        }
+        for marker in found {
+            messages.push(format!(
+                "[package.metadata.{}] 提到了已删除的平台 {}，可能已过时",
+                section, marker
+            ));
+        }
+    }
+    messages
+}
+
+/// 被删除的 target 描述对应的平台名：triple 本身，或 cfg 表达式中在保留的目标上不成立的
+/// `target_os`、`target_arch`、`target_family` 取值和 `windows`（`not(...)` 中的条件不计）
+fn removed_platform_names(spec: &str, filter: &TargetFilter) -> Vec<String> {
+    if !spec.starts_with("cfg(") {
+        return vec![spec.to_string()];
+    }
+    let mut names = Vec::new();
+    if let Ok(expr) = cfg::parse(spec) {
+        collect_names(&expr, filter, &mut names);
+    }
+    names
+}
+
+fn collect_names(expr: &CfgExpr, filter: &TargetFilter, names: &mut Vec<String>) {
+    match expr {
+        CfgExpr::All(exprs) | CfgExpr::Any(exprs) => {
+            for expr in exprs {
+                collect_names(expr, filter, names);
+            }
+        }
+        CfgExpr::Not(_) => {}
+        CfgExpr::Name(name) if name == "windows" && !expr.matches(filter) => {
+            names.push(name.clone());
+        }
+        CfgExpr::KeyValue(key, value)
+            if matches!(key.as_str(), "target_os" | "target_arch" | "target_family")
+                && !expr.matches(filter) =>
+        {
+            names.push(value.clone());
+        }
+        _ => {}
+    }
+}
+
+fn collect_strings<'a>(item: &'a Item, strings: &mut Vec<&'a str>) {
+    match item {
+        Item::Value(value) => collect_value_strings(value, strings),
+        Item::Table(table) => {
+            for (_, item) in table.iter() {
+                collect_strings(item, strings);
+            }
+        }
+        Item::ArrayOfTables(array) => {
+            for table in array.iter() {
+                for (_, item) in table.iter() {
+                    collect_strings(item, strings);
+                }
+            }
+        }
+        Item::None => {}
+    }
+}
+
+fn collect_value_strings<'a>(value: &'a Value, strings: &mut Vec<&'a str>) {
+    match value {
+        Value::String(s) => strings.push(s.value()),
+        Value::Array(array) => {
+            for value in array.iter() {
+                collect_value_strings(value, strings);
+            }
+        }
+        Value::InlineTable(table) => {
+            for (_, value) in table.iter() {
+                collect_value_strings(value, strings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 字符串是否提到了平台名：triple 按子串匹配，平台名按完整的词匹配（`ios` 不匹配 `bios`）
+fn mentions(s: &str, marker: &str) -> bool {
+    if marker.contains('-') {
+        return s.contains(marker);
+    }
+    s.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word.eq_ignore_ascii_case(marker))
+}
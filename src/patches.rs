@@ -0,0 +1,145 @@
+use crate::{Operation, ProcessReport};
+use cargo_toml::{DepsSet, Manifest};
+use std::collections::BTreeSet;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+/// 依赖表的名称
+const DEP_KINDS: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// 删除 `[patch.<source>]` 和 `[replace]` 中指向已删除依赖的条目，见 [`crate::Processor::prune_patches`]
+///
+/// 只删除处理前在清单中声明、处理后不再声明（包括 dev-dependencies 和 `[workspace.dependencies]`）的包，
+/// 按包名比较（`package = "..."` 重命名的依赖使用实际的包名）。间接依赖对这些包的使用无法判断，不考虑
+pub(crate) fn prune_patches(
+    doc: &mut DocumentMut,
+    manifest: &Manifest,
+    report: &mut ProcessReport,
+) {
+    let surviving = packages_in_doc(doc);
+    let removed: BTreeSet<String> = packages_in_manifest(manifest)
+        .into_iter()
+        .filter(|package| !surviving.contains(package))
+        .collect();
+    if removed.is_empty() {
+        return;
+    }
+
+    if let Some(patch) = doc.get_mut("patch").and_then(Item::as_table_like_mut) {
+        let sources: Vec<String> = patch.iter().map(|(source, _)| source.to_string()).collect();
+        for source in sources {
+            let Some(entries) = patch.get_mut(&source).and_then(Item::as_table_like_mut) else {
+                continue;
+            };
+            let names: Vec<String> = entries
+                .iter()
+                .filter(|(name, item)| removed.contains(package_of(name, item)))
+                .map(|(name, _)| name.to_string())
+                .collect();
+            for name in names {
+                entries.remove(&name);
+                report
+                    .removed_patches
+                    .push(format!("[patch.{}] {}", source, name));
+                report.operations.push(Operation::RemovePatch {
+                    source: source.clone(),
+                    name,
+                });
+            }
+            if entries.is_empty() {
+                patch.remove(&source);
+            }
+        }
+        if patch.is_empty() {
+            doc.remove("patch");
+        }
+    }
+
+    if let Some(replace) = doc.get_mut("replace").and_then(Item::as_table_like_mut) {
+        let specs: Vec<String> = replace
+            .iter()
+            .filter(|(spec, _)| removed.contains(spec_package(spec)))
+            .map(|(spec, _)| spec.to_string())
+            .collect();
+        for spec in specs {
+            replace.remove(&spec);
+            report.removed_patches.push(format!("[replace] {}", spec));
+            report.operations.push(Operation::RemoveReplace(spec));
+        }
+        if replace.is_empty() {
+            doc.remove("replace");
+        }
+    }
+}
+
+/// 依赖声明对应的包名：`package = "..."` 重命名时为实际的包名，否则为键名
+fn package_of<'a>(name: &'a str, item: &'a Item) -> &'a str {
+    item.get("package").and_then(Item::as_str).unwrap_or(name)
+}
+
+/// `[replace]` 键（包 ID 规格，例如 `foo:0.1.0`、`foo@0.1.0`、`https://...#foo@0.1.0`）中的包名
+fn spec_package(spec: &str) -> &str {
+    let spec = spec.rsplit_once('#').map_or(spec, |(_, fragment)| fragment);
+    spec.split([':', '@']).next().unwrap_or(spec)
+}
+
+/// 清单中声明的所有依赖的包名
+fn packages_in_manifest(manifest: &Manifest) -> BTreeSet<String> {
+    let mut tables: Vec<&DepsSet> = vec![
+        &manifest.dependencies,
+        &manifest.dev_dependencies,
+        &manifest.build_dependencies,
+    ];
+    for target in manifest.target.values() {
+        tables.extend([
+            &target.dependencies,
+            &target.dev_dependencies,
+            &target.build_dependencies,
+        ]);
+    }
+    if let Some(workspace) = &manifest.workspace {
+        tables.push(&workspace.dependencies);
+    }
+    tables
+        .into_iter()
+        .flat_map(|table| {
+            table
+                .iter()
+                .map(|(name, dep)| dep.package().unwrap_or(name).to_string())
+        })
+        .collect()
+}
+
+/// 文档中仍然声明的所有依赖的包名
+fn packages_in_doc(doc: &DocumentMut) -> BTreeSet<String> {
+    fn collect(table: &dyn TableLike, packages: &mut BTreeSet<String>) {
+        for kind in DEP_KINDS {
+            if let Some(deps) = table.get(kind).and_then(Item::as_table_like) {
+                packages.extend(
+                    deps.iter()
+                        .map(|(name, item)| package_of(name, item).to_string()),
+                );
+            }
+        }
+    }
+
+    let mut packages = BTreeSet::new();
+    collect(doc.as_table(), &mut packages);
+    if let Some(targets) = doc.get("target").and_then(Item::as_table_like) {
+        for (_, config) in targets.iter() {
+            if let Some(config) = config.as_table_like() {
+                collect(config, &mut packages);
+            }
+        }
+    }
+    if let Some(deps) = doc
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+        .and_then(Item::as_table_like)
+    {
+        packages.extend(
+            deps.iter()
+                .map(|(name, item)| package_of(name, item).to_string()),
+        );
+    }
+    packages
+}
@@ -0,0 +1,210 @@
+use crate::{FeatureMode, Operation, ProcessOptions, ProcessReport};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use toml_edit::{Array, DocumentMut, Item, TableLike};
+
+const DEP_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// 读取 path 依赖自身的清单，删除依赖方启用的、只在非 Linux 平台生效的 feature
+///
+/// 用同样的选项处理依赖的清单（开启 [`crate::Processor::prune_empty_features`]），
+/// 处理后变为空数组的 feature 视为平台特定的。这些 feature 会从依赖声明的 `features = [...]`
+/// 以及本清单 `[features]` 中的 `dep/feature`、`dep?/feature` 条目中删除。
+/// 只支持 path 依赖，依赖清单无法读取时给出警告并跳过
+pub(crate) fn strip_platform_dep_features(
+    doc: &mut DocumentMut,
+    manifest_dir: &Path,
+    options: &ProcessOptions,
+    report: &mut ProcessReport,
+) {
+    let dep_options = ProcessOptions {
+        feature_mode: FeatureMode::Apply,
+        prune_empty_features: true,
+        resolve_path_deps: false,
+        header: false,
+        require_content: false,
+        no_speculative: false,
+        ..options.clone()
+    };
+
+    let mut resolver = Resolver {
+        manifest_dir,
+        options: &dep_options,
+        emptied: BTreeMap::new(),
+        by_dep: BTreeMap::new(),
+    };
+
+    resolver.strip_table(doc.as_table_mut(), None, report);
+    if let Some(targets) = doc.get_mut("target").and_then(Item::as_table_like_mut) {
+        for (spec, target) in targets.iter_mut() {
+            if let Some(target) = target.as_table_like_mut() {
+                resolver.strip_table(target, Some(spec.get()), report);
+            }
+        }
+    }
+
+    let by_dep = resolver.by_dep;
+    if by_dep.is_empty() {
+        return;
+    }
+    if let Some(features) = doc.get_mut("features").and_then(Item::as_table_like_mut) {
+        strip_feature_refs(features, &by_dep, report);
+        report.emptied_features.sort();
+    }
+}
+
+struct Resolver<'a> {
+    manifest_dir: &'a Path,
+    options: &'a ProcessOptions,
+    /// path -> 依赖清单中被清空的 feature
+    emptied: BTreeMap<String, BTreeSet<String>>,
+    /// 依赖名 -> 被清空的 feature，用于清理本清单 [features] 中的引用
+    by_dep: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Resolver<'_> {
+    fn strip_table(
+        &mut self,
+        table: &mut dyn TableLike,
+        target: Option<&str>,
+        report: &mut ProcessReport,
+    ) {
+        for kind in DEP_TABLES {
+            let Some(deps) = table.get_mut(kind).and_then(Item::as_table_like_mut) else {
+                continue;
+            };
+            for (name, dep) in deps.iter_mut() {
+                let Some(dep) = dep.as_table_like_mut() else {
+                    continue;
+                };
+                let Some(path) = dep.get("path").and_then(Item::as_str).map(str::to_string) else {
+                    continue;
+                };
+                let emptied = self.emptied_features(&path, report);
+                if emptied.is_empty() {
+                    continue;
+                }
+                self.by_dep
+                    .entry(name.get().to_string())
+                    .or_default()
+                    .extend(emptied.iter().cloned());
+
+                let Some(features) = dep.get_mut("features").and_then(Item::as_array_mut) else {
+                    continue;
+                };
+                let mut stripped = Vec::new();
+                features.retain(|feature| match feature.as_str() {
+                    Some(feature) if emptied.contains(feature) => {
+                        stripped.push(feature.to_string());
+                        false
+                    }
+                    _ => true,
+                });
+                // 删除第一项后，新的第一项不应保留原来的前导空格
+                if let Some(first) = features.get_mut(0) {
+                    first.decor_mut().set_prefix("");
+                }
+                for feature in stripped {
+                    report.warnings.push(format!(
+                        "依赖 {} 的 feature {} 只在非 Linux 平台生效，已从 features 中删除",
+                        name.get(),
+                        feature
+                    ));
+                    report.operations.push(Operation::StripDepFeature {
+                        target: target.map(str::to_string),
+                        kind: kind.to_string(),
+                        name: name.get().to_string(),
+                        feature,
+                    });
+                }
+            }
+        }
+    }
+
+    /// 处理 path 依赖的清单，返回处理后变为空数组的 feature
+    fn emptied_features(&mut self, path: &str, report: &mut ProcessReport) -> BTreeSet<String> {
+        if let Some(emptied) = self.emptied.get(path) {
+            return emptied.clone();
+        }
+
+        let manifest_path = self.manifest_dir.join(path).join("Cargo.toml");
+        let emptied = match fs::read_to_string(&manifest_path)
+            .map_err(crate::ProcessError::from)
+            .and_then(|content| self.options.run(&content, None, &mut |_| {}))
+        {
+            Ok((output, dep_report)) => {
+                let output = output.parse::<DocumentMut>().ok();
+                let features = output
+                    .as_ref()
+                    .and_then(|doc| doc.get("features"))
+                    .and_then(Item::as_table_like);
+                dep_report
+                    .modified_features
+                    .into_keys()
+                    .filter(|feature| {
+                        features
+                            .and_then(|f| f.get(feature))
+                            .and_then(Item::as_array)
+                            .is_some_and(Array::is_empty)
+                    })
+                    .collect()
+            }
+            Err(e) => {
+                report.warnings.push(format!(
+                    "无法读取 path 依赖 {}: {}",
+                    manifest_path.display(),
+                    e
+                ));
+                BTreeSet::new()
+            }
+        };
+        self.emptied.insert(path.to_string(), emptied.clone());
+        emptied
+    }
+}
+
+/// 删除 `[features]` 中的 `dep/feature` 和 `dep?/feature` 条目（feature 已被清空）
+fn strip_feature_refs(
+    features: &mut dyn TableLike,
+    by_dep: &BTreeMap<String, BTreeSet<String>>,
+    report: &mut ProcessReport,
+) {
+    let is_emptied_ref = |item: &str| {
+        item.split_once('/').is_some_and(|(dep, feature)| {
+            by_dep
+                .get(dep.trim_end_matches('?'))
+                .is_some_and(|emptied| emptied.contains(feature))
+        })
+    };
+
+    for (name, value) in features.iter_mut() {
+        let Some(array) = value.as_array_mut() else {
+            continue;
+        };
+        let removed: Vec<String> = array
+            .iter()
+            .filter_map(|v| v.as_str())
+            .filter(|item| is_emptied_ref(item))
+            .map(str::to_string)
+            .collect();
+        if removed.is_empty() {
+            continue;
+        }
+        crate::retain_feature_items(array, |item| !is_emptied_ref(item));
+        for item in &removed {
+            report.operations.push(Operation::StripFeatureItem {
+                feature: name.to_string(),
+                item: item.clone(),
+            });
+        }
+        if array.is_empty() && !report.emptied_features.iter().any(|f| f == name.get()) {
+            report.emptied_features.push(name.to_string());
+        }
+        report
+            .modified_features
+            .entry(name.to_string())
+            .or_default()
+            .extend(removed);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::ProcessError;
+use cfg_expr::targets::get_builtin_target_by_triple;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// `rustc -vV` 的查询结果，每个进程只调用一次 rustc
+static RUSTC_HOST: OnceLock<Result<String, String>> = OnceLock::new();
+
+/// 当前工具链的默认目标 triple（`rustc -vV` 输出中的 `host:` 行）
+///
+/// 优先使用 `RUSTC` 环境变量指定的编译器，与 cargo 的行为一致。
+/// 找不到 rustc、输出无法解析或 triple 不在 cfg-expr 内置目标列表中时报错
+pub fn rustc_host_triple() -> Result<String, ProcessError> {
+    RUSTC_HOST
+        .get_or_init(query_rustc_host)
+        .clone()
+        .map_err(ProcessError::RustcError)
+}
+
+fn query_rustc_host() -> Result<String, String> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(&rustc)
+        .arg("-vV")
+        .output()
+        .map_err(|e| format!("无法运行 {} -vV: {}（请确认已安装 Rust 工具链）", rustc, e))?;
+    if !output.status.success() {
+        return Err(format!("{} -vV 执行失败: {}", rustc, output.status));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let triple =
+        parse_rustc_host(&stdout).ok_or_else(|| format!("{} -vV 的输出中没有 host 行", rustc))?;
+    if get_builtin_target_by_triple(&triple).is_none() {
+        return Err(format!("不支持的目标 triple: {}", triple));
+    }
+    Ok(triple)
+}
+
+/// 从 `rustc -vV` 的输出中提取 `host:` 行
+///
+/// ```text
+/// rustc 1.85.0 (4d91de4e4 2025-02-17)
+/// binary: rustc
+/// host: x86_64-unknown-linux-gnu
+/// release: 1.85.0
+/// ```
+pub fn parse_rustc_host(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("host:"))
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "rustc 1.85.0 (4d91de4e4 2025-02-17)
+binary: rustc
+commit-hash: 4d91de4e48198da2e33413efdcd9cd2cc0c46688
+commit-date: 2025-02-17
+host: x86_64-unknown-linux-gnu
+release: 1.85.0
+LLVM version: 19.1.7
+";
+
+    #[test]
+    fn parses_host_line() {
+        assert_eq!(
+            parse_rustc_host(SAMPLE).as_deref(),
+            Some("x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            parse_rustc_host("host:   riscv64gc-unknown-linux-gnu\r\n").as_deref(),
+            Some("riscv64gc-unknown-linux-gnu")
+        );
+    }
+
+    #[test]
+    fn missing_host_line() {
+        let without_host: String = SAMPLE
+            .lines()
+            .filter(|line| !line.starts_with("host:"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert_eq!(parse_rustc_host(&without_host), None);
+        assert_eq!(parse_rustc_host("host:\n"), None);
+        assert_eq!(parse_rustc_host(""), None);
+    }
+}
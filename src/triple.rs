@@ -0,0 +1,73 @@
+use cfg_expr::targets::ALL_BUILTINS;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// 内置目标中出现过的 vendor，用于区分三段式 triple 的第二段是 vendor（`x86_64-unknown-linux`）
+/// 还是系统（`aarch64-linux-android`）
+static KNOWN_VENDORS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    ALL_BUILTINS
+        .iter()
+        .filter_map(|target| target.vendor.as_ref().map(|vendor| vendor.as_str()))
+        .collect()
+});
+
+/// 按 `arch-vendor-os-env` 拆分的目标 triple，用于判断 `cfg_expr` 不认识的 triple
+///
+/// 四段及以上时依次为架构、vendor、系统和环境（之后的段忽略）；三段时第二段是已知的 vendor 才当作 vendor，
+/// 否则为系统和环境；两段时为架构和系统
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TripleParts<'a> {
+    /// triple 的第一段，例如 `riscv64gc`
+    pub(crate) arch: &'a str,
+    pub(crate) vendor: Option<&'a str>,
+    pub(crate) os: Option<&'a str>,
+    pub(crate) env: Option<&'a str>,
+}
+
+impl<'a> TripleParts<'a> {
+    pub(crate) fn parse(triple: &'a str) -> Self {
+        let mut parts = triple.split('-');
+        let arch = parts.next().unwrap_or("");
+        let rest: Vec<&str> = parts.collect();
+        let has_vendor = match rest.len() {
+            0 | 1 => false,
+            2 => KNOWN_VENDORS.contains(rest[0]),
+            _ => true,
+        };
+        let (vendor, rest) = if has_vendor {
+            (Some(rest[0]), &rest[1..])
+        } else {
+            (None, &rest[..])
+        };
+        TripleParts {
+            arch,
+            vendor,
+            os: rest.first().copied(),
+            env: rest.get(1).copied(),
+        }
+    }
+
+    /// `target_arch` 的取值：内置目标中有相同首段的 triple 时使用它的架构（`riscv64gc` 为 `riscv64`，
+    /// `i686` 为 `x86`），否则为首段本身
+    pub(crate) fn target_arch(&self) -> &'a str {
+        let prefix = format!("{}-", self.arch);
+        ALL_BUILTINS
+            .iter()
+            .find(|target| target.triple.as_str().starts_with(&prefix))
+            .map_or(self.arch, |target| target.arch.as_str())
+    }
+
+    /// 系统是否为 `os`（按前缀比较，例如 `wasip1` 属于 `wasi`）；`linux` 不包括环境为 `android` 的 triple
+    pub(crate) fn has_os(&self, os: &str) -> bool {
+        self.os.is_some_and(|own| own.starts_with(os))
+            && !(os == "linux" && self.env.is_some_and(|env| env.starts_with("android")))
+    }
+
+    /// 环境是否为 `env`（按前缀比较，例如 `gnueabihf` 属于 `gnu`），空字符串表示没有环境
+    pub(crate) fn has_env(&self, env: &str) -> bool {
+        match self.env {
+            Some(own) => !env.is_empty() && own.starts_with(env),
+            None => env.is_empty(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::ProcessError;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 清单文件名
+const MANIFEST_NAME: &str = "Cargo.toml";
+
+/// 遍历时跳过的目录（构建产物和版本控制目录）
+const SKIPPED_DIRS: &[&str] = &["target", ".git"];
+
+/// 递归查找目录下的所有 `Cargo.toml`
+///
+/// `follow_symlinks` 为 true 时跟随符号链接，但按 `fs::canonicalize` 后的真实路径去重，
+/// 同一个文件只返回一次，符号链接形成的环也不会导致死循环；为 false 时跳过所有符号链接。
+/// 返回的路径按字典序排列
+pub fn find_manifests(root: &Path, follow_symlinks: bool) -> Result<Vec<PathBuf>, ProcessError> {
+    let mut visited_dirs = HashSet::new();
+    let mut seen_files = HashSet::new();
+    let mut manifests = Vec::new();
+    walk_dir(
+        root,
+        follow_symlinks,
+        &mut visited_dirs,
+        &mut seen_files,
+        &mut manifests,
+    )?;
+    manifests.sort();
+    Ok(manifests)
+}
+
+fn walk_dir(
+    dir: &Path,
+    follow_symlinks: bool,
+    visited_dirs: &mut HashSet<PathBuf>,
+    seen_files: &mut HashSet<PathBuf>,
+    manifests: &mut Vec<PathBuf>,
+) -> Result<(), ProcessError> {
+    if !visited_dirs.insert(fs::canonicalize(dir)?) {
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_symlink() && !follow_symlinks {
+            continue;
+        }
+
+        // 对符号链接使用目标的元数据；悬空链接直接跳过
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if metadata.is_dir() {
+            let skipped = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| SKIPPED_DIRS.contains(&name));
+            if !skipped {
+                walk_dir(&path, follow_symlinks, visited_dirs, seen_files, manifests)?;
+            }
+        } else if entry.file_name() == MANIFEST_NAME && seen_files.insert(fs::canonicalize(&path)?)
+        {
+            manifests.push(path);
+        }
+    }
+
+    Ok(())
+}
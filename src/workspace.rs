@@ -0,0 +1,167 @@
+use crate::{ProcessError, ProcessReport, Processor, TargetFilter};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use toml_edit::{DocumentMut, Item};
+
+/// 处理 workspace 的所有成员，每个成员的 `Cargo.toml` 原地覆盖
+///
+/// 读取根清单的 `[workspace] members`（支持 `*`、`?` 和 `**` 通配符），跳过 `exclude` 中的路径
+/// 和没有 `Cargo.toml` 的目录。根清单在所有成员之后处理（包括没有 `[package]` 的虚拟根清单），
+/// `[workspace.dependencies]` 中没有成员使用的平台依赖会被删除。
+/// 没有平台特定配置的清单不会被修改，报告中 `changed` 为 false。
+/// 返回根清单和每个成员的清单路径及处理报告，根清单在最前，成员按路径排序
+pub fn process_workspace(
+    root_manifest_path: &str,
+    filter: &TargetFilter,
+) -> Result<Vec<(PathBuf, ProcessReport)>, ProcessError> {
+    let root_manifest = Path::new(root_manifest_path);
+    let content = fs::read_to_string(root_manifest)?;
+    let doc = content.parse::<DocumentMut>()?;
+    let workspace = doc
+        .get("workspace")
+        .and_then(Item::as_table_like)
+        .ok_or_else(|| {
+            ProcessError::ConfigError(format!(
+                "{} 不是 workspace 根清单（没有 [workspace]）",
+                root_manifest_path
+            ))
+        })?;
+    let root = match root_manifest.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let excluded: Vec<PathBuf> = string_list(workspace.get("exclude"), "workspace.exclude")?
+        .iter()
+        .map(|path| normalize(&root.join(path)))
+        .collect();
+
+    let root_manifest = normalize(root_manifest);
+    let mut manifests = BTreeSet::new();
+    for pattern in string_list(workspace.get("members"), "workspace.members")? {
+        let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+        let mut dirs = Vec::new();
+        expand(root, &components, &mut dirs)?;
+        for dir in dirs {
+            let dir = normalize(&dir);
+            if excluded.iter().any(|excluded| dir.starts_with(excluded)) {
+                continue;
+            }
+            let manifest = dir.join("Cargo.toml");
+            if manifest.is_file() && manifest != root_manifest {
+                manifests.insert(manifest);
+            }
+        }
+    }
+
+    let mut processor = Processor::new().filter(filter.clone());
+    let mut reports = Vec::new();
+    let mut member_deps = BTreeSet::new();
+    for manifest in manifests {
+        let path = manifest.to_string_lossy().into_owned();
+        let report = processor.process_file(&path, &path)?;
+        member_deps.extend(report.surviving_deps.iter().cloned());
+        reports.push((manifest, report));
+    }
+
+    // 根清单最后处理，[workspace.dependencies] 中仍有成员使用的依赖不删除
+    processor.options.workspace_member_deps = Some(member_deps);
+    let path = root_manifest.to_string_lossy().into_owned();
+    let report = processor.process_file(&path, &path)?;
+    reports.insert(0, (root_manifest, report));
+    Ok(reports)
+}
+
+fn string_list(item: Option<&Item>, key: &str) -> Result<Vec<String>, ProcessError> {
+    let Some(item) = item else {
+        return Ok(Vec::new());
+    };
+    let error = || ProcessError::ConfigError(format!("{} 必须是字符串数组", key));
+    item.as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|v| v.as_str().map(str::to_string).ok_or_else(error))
+        .collect()
+}
+
+/// 按路径组件展开通配符，收集匹配的目录
+fn expand(base: &Path, components: &[&str], dirs: &mut Vec<PathBuf>) -> Result<(), ProcessError> {
+    let Some((&first, rest)) = components.split_first() else {
+        if base.is_dir() {
+            dirs.push(base.to_path_buf());
+        }
+        return Ok(());
+    };
+
+    if !first.contains(['*', '?']) {
+        return expand(&base.join(first), rest, dirs);
+    }
+    if !base.is_dir() {
+        return Ok(());
+    }
+
+    // `**` 匹配零个或多个目录
+    if first == "**" {
+        expand(base, rest, dirs)?;
+    }
+    let mut entries: Vec<_> = fs::read_dir(base)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if !entry.path().is_dir() {
+            continue;
+        }
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if first == "**" {
+            expand(&entry.path(), components, dirs)?;
+        } else if wildcard_match(first, &name) {
+            expand(&entry.path(), rest, dirs)?;
+        }
+    }
+    Ok(())
+}
+
+/// `*` 匹配任意个字符，`?` 匹配一个字符
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // matched[j] 为当前的模式前缀能否匹配 name[..j]
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for &p in &pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+/// 去掉路径中的 `.` 和 `..`，不访问文件系统，用于比较 `exclude`
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                let at_start = matches!(
+                    normalized.components().next_back(),
+                    None | Some(Component::ParentDir)
+                );
+                if at_start {
+                    normalized.push("..");
+                } else {
+                    normalized.pop();
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,571 @@
+// 库接口的集成测试
+
+mod common;
+
+use common::{TempDir, WINDOWS_MANIFEST};
+use pre::{Processor, StripConfig};
+use toml_edit::DocumentMut;
+
+fn parse(content: &str) -> (DocumentMut, cargo_toml::Manifest) {
+    let doc = content.parse::<DocumentMut>().unwrap();
+    let manifest = cargo_toml::Manifest::from_slice(content.as_bytes()).unwrap();
+    (doc, manifest)
+}
+
+/// 默认的 StripConfig 与不带配置的函数结果相同，构建器设置的选项与 Processor 相同
+#[test]
+fn strip_config_matches_existing_entry_points() {
+    let (mut expected, manifest) = parse(WINDOWS_MANIFEST);
+    pre::process_toml_doc(&mut expected, &manifest);
+    let (mut doc, _) = parse(WINDOWS_MANIFEST);
+    let report = pre::process_toml_doc_with_config(&mut doc, &manifest, &StripConfig::default());
+    assert_eq!(doc.to_string(), expected.to_string());
+    assert_eq!(report.removed_targets, ["cfg(windows)"]);
+
+    let config = StripConfig::builder().remove_empty_features(true).build();
+    let dir = TempDir::new("strip-config");
+    let input = dir.write("Cargo.toml", WINDOWS_MANIFEST);
+    let output = dir.path().join("out.toml");
+    pre::process_toml_file_with_config(input.to_str().unwrap(), output.to_str().unwrap(), &config)
+        .unwrap();
+    let (expected, _) = Processor::new()
+        .remove_empty_features(true)
+        .run(WINDOWS_MANIFEST)
+        .unwrap();
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+    assert!(!expected.contains("registry"));
+
+    // 带报告的文件接口与默认配置的结果相同
+    let reported_output = dir.path().join("reported.toml");
+    let reported =
+        pre::process_toml_file_reported(input.to_str().unwrap(), reported_output.to_str().unwrap())
+            .unwrap();
+    let default_output = dir.path().join("default.toml");
+    let with_default = pre::process_toml_file_with_config(
+        input.to_str().unwrap(),
+        default_output.to_str().unwrap(),
+        &StripConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(reported, with_default);
+    assert_eq!(
+        std::fs::read_to_string(&reported_output).unwrap(),
+        std::fs::read_to_string(&default_output).unwrap()
+    );
+
+    // 转回 Processor 后选项不变
+    let processor = Processor::from(config);
+    assert_eq!(
+        processor.fingerprint(),
+        Processor::new().remove_empty_features(true).fingerprint()
+    );
+}
+
+/// 依赖删除回调只收到最终被删除的依赖：仍被保留的 target 配置或顶层依赖使用的依赖不报告
+#[test]
+fn dep_removed_callback_reports_final_removals() {
+    let content = r#"[package]
+name = "callback"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+libc = "0.2"
+
+[target.'cfg(windows)'.dependencies]
+winreg = "0.52"
+libc = "0.2"
+shared = "1.0"
+
+[target.'cfg(unix)'.dependencies]
+shared = "1.0"
+
+[target.'cfg(target_os = "macos")'.dependencies]
+core-foundation = "0.9"
+"#;
+    let removed = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let sink = removed.clone();
+    let mut processor =
+        Processor::new().on_dep_removed(move |dep| sink.borrow_mut().push(dep.to_string()));
+    let (output, report) = processor.run(content).unwrap();
+
+    let mut expected: Vec<String> = report
+        .removed_by_category
+        .values()
+        .flatten()
+        .cloned()
+        .collect();
+    expected.sort();
+    assert_eq!(*removed.borrow(), ["core-foundation", "winreg"]);
+    assert_eq!(*removed.borrow(), expected);
+    // libc 和 shared 的声明随 target 配置一起删除，但仍在其他位置声明，不报告
+    assert!(output.contains("libc = \"0.2\""));
+    assert!(output.contains("shared = \"1.0\""));
+}
+
+/// into_fn 得到的闭包可以重复用于多个清单，结果与每次单独处理相同
+#[test]
+fn into_fn_maps_over_manifests() {
+    let manifests: Vec<String> = ["windows", "target_os = \"macos\"", "unix"]
+        .iter()
+        .enumerate()
+        .map(|(i, spec)| {
+            format!(
+                "[package]\nname = \"m{}\"\nversion = \"0.1.0\"\n\n[target.'cfg({})'.dependencies]\ndep-{} = \"1.0\"\n",
+                i, spec, i
+            )
+        })
+        .collect();
+
+    let process = Processor::new().into_fn();
+    let results: Vec<_> = manifests
+        .iter()
+        .map(|content| process(content).unwrap())
+        .collect();
+    for (content, (output, _)) in manifests.iter().zip(&results) {
+        assert_eq!(*output, pre::process_toml_string(content).unwrap());
+    }
+    let removed: Vec<usize> = results
+        .iter()
+        .map(|(_, report)| report.removed_targets.len())
+        .collect();
+    assert_eq!(removed, [1, 1, 0]);
+
+    // 闭包可以跨线程共享
+    let process = std::sync::Arc::new(process);
+    let handles: Vec<_> = manifests
+        .into_iter()
+        .map(|content| {
+            let process = process.clone();
+            std::thread::spawn(move || process(&content).unwrap().1.changed)
+        })
+        .collect();
+    let changed: Vec<bool> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(changed, [true, true, false]);
+}
+
+/// 删除的已知平台依赖按分类记录：windows-sys 在 windows 分类中
+#[test]
+fn removed_by_category_buckets_windows_sys() {
+    let content = r#"[package]
+name = "category"
+version = "0.1.0"
+edition = "2021"
+
+[target.'cfg(windows)'.dependencies]
+windows-sys = "0.59"
+my-win-helper = "1.0"
+
+[target.'cfg(target_os = "macos")'.dependencies]
+core-foundation = "0.9"
+"#;
+    let (_, report) = Processor::new().run(content).unwrap();
+    let windows = &report.removed_by_category["windows"];
+    assert!(windows.contains(&"windows-sys".to_string()), "{:?}", report);
+    // 不在已知列表中的依赖按 target 配置的平台关键字分类
+    assert!(windows.contains(&"my-win-helper".to_string()));
+    assert!(
+        !report
+            .removed_by_category
+            .iter()
+            .any(|(category, deps)| category != "windows"
+                && deps.contains(&"windows-sys".to_string()))
+    );
+}
+
+/// 无需修改时不写入输出文件，目标只读（例如 Nix 构建沙箱）也能成功
+#[cfg(unix)]
+#[test]
+fn no_change_run_on_read_only_path_succeeds() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let content = "[package]\nname = \"clean\"\nversion = \"0.1.0\"\n\n[target.'cfg(unix)'.dependencies]\nlibc = \"0.2\"\n";
+    let dir = TempDir::new("read-only");
+    let file = dir.write("ro/Cargo.toml", content);
+    let ro_dir = file.parent().unwrap().to_path_buf();
+    let set_mode = |path: &std::path::Path, mode| {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap()
+    };
+    set_mode(&file, 0o444);
+    set_mode(&ro_dir, 0o555);
+    let modified = std::fs::metadata(&file).unwrap().modified().unwrap();
+
+    let path = file.to_str().unwrap();
+    let result = pre::process_toml_file(path, path);
+    let cli = common::pre([&file]);
+
+    // 恢复权限，以便删除临时目录
+    set_mode(&ro_dir, 0o755);
+    set_mode(&file, 0o644);
+    result.unwrap();
+    assert!(cli.status.success(), "{}", common::stderr(&cli));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), content);
+    // 以 root 运行时只读权限不生效，用修改时间确认没有写入
+    assert_eq!(
+        std::fs::metadata(&file).unwrap().modified().unwrap(),
+        modified
+    );
+}
+
+/// 语法错误报告出错的行列号（从 1 开始，列按字符计）
+#[test]
+fn parse_error_reports_line_and_column() {
+    // 第 4 行的值不是合法的 TOML 值，错误位于第 11 列的 `]`
+    let content = "[package]\nname = \"loc\"\n# 值\nversion = ]\n";
+    let error = pre::process_toml_string(content).unwrap_err();
+    assert!(
+        matches!(error, pre::ProcessError::ParseError(_)),
+        "{:?}",
+        error
+    );
+    let span = error.span().unwrap();
+    assert_eq!(&content[span.start..span.start + 1], "]");
+    assert_eq!(error.parse_location(content), Some((4, 11)));
+
+    // 多字节字符在同一行时，列按字符计
+    let content = "[package]\nname = \"名字\" x\n";
+    let error = pre::process_toml_string(content).unwrap_err();
+    assert_eq!(error.parse_location(content), Some((2, 13)));
+
+    // cargo_toml 报告的类型错误同样指向出错的值
+    let content = "[package]\nname = 1\n";
+    let error = pre::process_toml_string(content).unwrap_err();
+    assert!(
+        matches!(error, pre::ProcessError::CargoTomlError(_)),
+        "{:?}",
+        error
+    );
+    assert_eq!(error.parse_location(content), Some((2, 8)));
+}
+
+/// 无效的 UTF-8 以 InvalidUtf8 返回，span 指向无效的字节；有效的字节输入与字符串处理结果相同
+#[test]
+fn process_toml_bytes_rejects_invalid_utf8() {
+    let filter = pre::TargetFilter::default();
+    let mut input = b"[package]\nname = \"bad".to_vec();
+    let offset = input.len();
+    input.extend_from_slice(b"\xff\"\nversion = \"0.1.0\"\n");
+
+    let error = pre::process_toml_bytes(&input, &filter).unwrap_err();
+    assert!(
+        matches!(error, pre::ProcessError::InvalidUtf8(_)),
+        "{:?}",
+        error
+    );
+    assert_eq!(error.span(), Some(offset..offset + 1));
+
+    let output = pre::process_toml_bytes(WINDOWS_MANIFEST.as_bytes(), &filter).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        pre::process_toml_string(WINDOWS_MANIFEST).unwrap()
+    );
+}
+
+/// 已解析的 DocumentMut 和 Manifest 交给 process_document，结果与 process_toml_string 相同
+#[test]
+fn process_document_matches_process_toml_string() {
+    let content = format!(
+        "{}\n[target.'cfg(target_os = \"macos\")'.dependencies]\ncore-foundation = \"0.9\"\n\n[target.'cfg(unix)'.dependencies]\nnix = \"0.29\"\n",
+        WINDOWS_MANIFEST
+    );
+    let (mut doc, manifest) = parse(&content);
+    let report = Processor::new()
+        .process_document(&mut doc, &manifest)
+        .unwrap();
+    assert_eq!(doc.to_string(), pre::process_toml_string(&content).unwrap());
+
+    let (_, expected) = Processor::new().run(&content).unwrap();
+    assert_eq!(report.removed_targets, expected.removed_targets);
+    assert_eq!(report.removed_deps, expected.removed_deps);
+    assert_eq!(report.modified_features, expected.modified_features);
+    // 文档形式的接口不比较输入输出，changed 始终为 false
+    assert!(!report.changed && expected.changed);
+}
+
+/// 原子写入失败时（这里目标是目录，重命名失败）删除临时文件，不留下 .<文件名>.pre-tmp-<pid>
+#[test]
+fn atomic_write_cleans_up_temp_file_on_failure() {
+    let dir = TempDir::new("atomic-failure");
+    dir.write("Cargo.toml/keep", "");
+    let target = dir.path().join("Cargo.toml");
+
+    let result = pre::write_output_file(target.to_str().unwrap(), "[package]\n", true);
+    assert!(result.is_err());
+    let leftovers: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.contains(".pre-tmp-"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+    assert!(target.join("keep").exists());
+}
+
+/// 顶层表的预检查：表头、带引号的表头、顶层键都算；只在字符串、注释或其他表的键中出现不算
+#[test]
+fn has_top_level_table_scans_headers_and_keys() {
+    for content in [
+        "[package]\nname = \"a\"\n\n[target.'cfg(windows)'.dependencies]\nwinreg = \"0.52\"\n",
+        "[target]\n",
+        "  [ \"target\" . x86_64-pc-windows-msvc.dependencies ]\nwinapi = \"0.3\"\n",
+        "[[target]]\n",
+        "target.'cfg(unix)'.dependencies.libc = \"0.2\"\n[package]\nname = \"a\"\n",
+        "target = { \"cfg(unix)\" = { dependencies = { libc = \"0.2\" } } }\n",
+        // 多行字符串需要解析确认，这里的表头在字符串之外
+        "[package]\ndescription = \"\"\"\nx\n\"\"\"\n\n[target.'cfg(unix)'.dependencies]\n",
+    ] {
+        assert!(pre::has_top_level_table(content, "target"), "{}", content);
+    }
+
+    for content in [
+        "[package]\nname = \"target\"\n# [target.'cfg(windows)'.dependencies]\n",
+        "[package]\nname = \"a\"\n\n[targets]\n\n[lib.target]\n",
+        "[build]\ntarget = \"x86_64-unknown-linux-gnu\"\n",
+        "[package]\ndescription = '''\n[target.'cfg(windows)'.dependencies]\n'''\n",
+    ] {
+        assert!(!pre::has_top_level_table(content, "target"), "{}", content);
+    }
+}
+
+/// 记录的操作序列化后再解析，重放到重新解析的文档上，结果与直接处理完全相同
+#[test]
+fn recorded_operations_replay_to_identical_output() {
+    for (content, mut processor) in [
+        (WINDOWS_MANIFEST, Processor::new()),
+        (
+            include_str!("../test/dotted-target-keys.toml"),
+            Processor::new(),
+        ),
+        (
+            include_str!("../test/target-inline-table.toml"),
+            Processor::new(),
+        ),
+        (
+            include_str!("../test/hoist-deps.toml"),
+            Processor::new().hoist_deps(true),
+        ),
+    ] {
+        let (output, report) = processor.run(content).unwrap();
+        assert!(!report.operations.is_empty());
+        let ops = pre::parse_operations(&pre::serialize_operations(&report.operations)).unwrap();
+        assert_eq!(ops, report.operations);
+
+        let (mut doc, _) = parse(content);
+        pre::replay(&mut doc, &ops);
+        assert_eq!(doc.to_string(), output);
+    }
+}
+
+/// 校验输出：正常处理的结果通过校验，与不校验的结果相同；
+/// 不清理 features 时留下指向已删除依赖的 `dep:winreg`，返回 ValidationError
+#[test]
+fn validate_rejects_dangling_feature_refs() {
+    for content in [
+        WINDOWS_MANIFEST,
+        include_str!("../test/dotted-target-keys.toml"),
+    ] {
+        let (validated, _) = Processor::new().validate(true).run(content).unwrap();
+        assert_eq!(validated, pre::process_toml_string(content).unwrap());
+    }
+
+    let error = Processor::new()
+        .validate(true)
+        .feature_mode(pre::FeatureMode::Skip)
+        .run(WINDOWS_MANIFEST)
+        .unwrap_err();
+    match error {
+        pre::ProcessError::ValidationError(message) => {
+            assert!(message.contains("dep:winreg"), "{}", message)
+        }
+        error => panic!("{:?}", error),
+    }
+    // 不校验时照常输出
+    let (output, _) = Processor::new()
+        .feature_mode(pre::FeatureMode::Skip)
+        .run(WINDOWS_MANIFEST)
+        .unwrap();
+    assert!(output.contains("registry = [\"dep:winreg\"]"));
+}
+
+/// 有自定义构建脚本且删除了 build-dependencies 时给出警告；没有 `build` 时不警告
+#[test]
+fn build_script_warning_for_removed_build_deps() {
+    let content = include_str!("../test/windows-build-deps.toml");
+    let (_, report) = Processor::new().run(content).unwrap();
+    assert!(
+        report.warnings.iter().any(|warning| warning
+            == "构建脚本 build.rs 可能依赖已删除的 build-dependencies: embed-resource"),
+        "{:?}",
+        report.warnings
+    );
+
+    let without_build = content.replace("build = \"build.rs\"\n", "");
+    assert_ne!(without_build, content);
+    let (_, report) = Processor::new().run(&without_build).unwrap();
+    assert!(!report.removed_deps.is_empty());
+    assert!(
+        !report
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("构建脚本")),
+        "{:?}",
+        report.warnings
+    );
+}
+
+/// 三引号的 target 键不会导致 panic：多行字符串不能作为键，返回解析错误；
+/// 单行字符串中带三个引号是合法的键，照常处理
+#[test]
+fn triple_quoted_target_keys_do_not_panic() {
+    let manifests = |key: &str| {
+        [
+            format!("[package]\nname = \"odd\"\nversion = \"0.1.0\"\n\n[target.{}.dependencies]\nwinapi = \"0.3\"\n", key),
+            format!("[package]\nname = \"odd\"\nversion = \"0.1.0\"\n\n[target]\n{} = {{ dependencies = {{ winapi = \"0.3\" }} }}\n", key),
+        ]
+    };
+    let process = |content: &str| {
+        std::panic::catch_unwind(|| pre::process_toml_string(content))
+            .unwrap_or_else(|_| panic!("处理时 panic: {}", content))
+    };
+
+    for key in [
+        "'''cfg(windows)'''",
+        "\"\"\"cfg(windows)\"\"\"",
+        "'''\ncfg(windows)\n'''",
+        "'''cfg(all(unix, '''",
+        "\"\"\"\"\"\"",
+    ] {
+        for content in manifests(key) {
+            let error = process(&content).unwrap_err();
+            assert!(
+                matches!(error, pre::ProcessError::ParseError(_)),
+                "{:?}",
+                error
+            );
+        }
+    }
+
+    for key in [
+        "\"\\\"\\\"\\\"cfg(windows)\\\"\\\"\\\"\"",
+        "'\"\"\"cfg(windows)\"\"\"'",
+    ] {
+        for content in manifests(key) {
+            assert!(process(&content).is_ok(), "{}", content);
+        }
+    }
+}
+
+/// surviving_deps 包含顶层和保留的 target 配置下的依赖与构建依赖，不包含被删除的依赖和 dev-dependencies
+#[test]
+fn surviving_deps_lists_kept_deps() {
+    let (_, report) = Processor::new().run(WINDOWS_MANIFEST).unwrap();
+    assert_eq!(report.surviving_deps, ["libc".to_string()].into());
+
+    let content = format!(
+        "{}\n[build-dependencies]\ncc = \"1\"\n\n[dev-dependencies]\ncriterion = \"0.5\"\n\n[target.'cfg(unix)'.dependencies]\nnix = \"0.29\"\n\n[target.'cfg(target_os = \"linux\")'.build-dependencies]\npkg-config = \"0.3\"\n\n[target.'cfg(target_os = \"macos\")'.dependencies]\ncore-foundation = \"0.9\"\n",
+        WINDOWS_MANIFEST
+    );
+    let (_, report) = Processor::new().run(&content).unwrap();
+    let surviving: Vec<&str> = report.surviving_deps.iter().map(String::as_str).collect();
+    assert_eq!(surviving, ["cc", "libc", "nix", "pkg-config"]);
+}
+
+/// features 用名称（而不是 dep:）引用可选依赖时有隐式 feature：依赖被删除时引用被清理，并警告隐式 feature 消失；
+/// 用 dep: 引用时没有隐式 feature，不警告
+#[test]
+fn removed_implicit_feature_is_reported() {
+    let content = WINDOWS_MANIFEST.replace("\"dep:winreg\"", "\"winreg\"");
+    let (doc, manifest) = parse(&content);
+    assert_eq!(
+        pre::check_feature_consistency(&doc, &manifest),
+        ["可选依赖 `winreg` 产生了隐式 feature，[features] 表中未声明"]
+    );
+
+    let (output, report) = Processor::new().run(&content).unwrap();
+    assert!(output.contains("registry = []"), "{}", output);
+    assert_eq!(
+        report.warnings,
+        ["可选依赖 `winreg` 已删除，它产生的隐式 feature `winreg` 不再存在"]
+    );
+
+    let (doc, manifest) = parse(WINDOWS_MANIFEST);
+    assert!(pre::check_feature_consistency(&doc, &manifest).is_empty());
+    let (_, report) = Processor::new().run(WINDOWS_MANIFEST).unwrap();
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+}
+
+/// analyze_manifest 列出会被删除的 target 配置、平台特定依赖和受影响的 features，结果与实际处理一致
+#[test]
+fn analyze_manifest_lists_platform_specific_parts() {
+    let content = format!(
+        "{}\n[target.'cfg(unix)'.dependencies]\nnix = \"0.29\"\n\n[target.'cfg(target_os = \"macos\")'.dev-dependencies]\ncore-foundation = \"0.9\"\n",
+        WINDOWS_MANIFEST
+    );
+    let (_, manifest) = parse(&content);
+    let analysis = pre::analyze_manifest(&manifest, &pre::TargetFilter::default());
+
+    assert_eq!(analysis.dependencies, ["libc"]);
+    let specs: Vec<(&str, bool)> = analysis
+        .targets
+        .iter()
+        .map(|target| (target.spec.as_str(), target.removed))
+        .collect();
+    assert_eq!(
+        specs,
+        [
+            ("cfg(target_os = \"macos\")", true),
+            ("cfg(unix)", false),
+            ("cfg(windows)", true)
+        ]
+    );
+    let windows = analysis
+        .targets
+        .iter()
+        .find(|target| target.spec == "cfg(windows)")
+        .unwrap();
+    assert_eq!(windows.dependencies, ["winreg"]);
+    assert!(windows.dev_dependencies.is_empty() && windows.build_dependencies.is_empty());
+    assert_eq!(analysis.targets[0].dev_dependencies, ["core-foundation"]);
+
+    let removed: Vec<&str> = analysis.removed_targets().collect();
+    let (_, report) = Processor::new().run(&content).unwrap();
+    let mut expected: Vec<&str> = report.removed_targets.iter().map(String::as_str).collect();
+    expected.sort();
+    assert_eq!(removed, expected);
+
+    assert!(
+        analysis.platform_deps.contains_key("winreg"),
+        "{:?}",
+        analysis
+    );
+    assert!(!analysis.platform_deps.contains_key("nix"));
+    assert!(!analysis.platform_deps.contains_key("libc"));
+    assert_eq!(
+        analysis.affected_features,
+        [("registry".to_string(), vec!["dep:winreg".to_string()])].into()
+    );
+}
+
+/// 顶层 dev-dependencies 中删除的已知平台依赖记录在报告中，但不参与 features 清理，也不通知回调；
+/// build-dependencies 中删除的照常参与
+#[test]
+fn top_level_dev_dep_removals_do_not_feed_feature_cleaning() {
+    let content = include_str!("../test/top-level-build-deps.toml");
+    let removed = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let sink = removed.clone();
+    let mut processor =
+        Processor::new().on_dep_removed(move |dep| sink.borrow_mut().push(dep.to_string()));
+    let (output, report) = processor.run(content).unwrap();
+
+    assert!(!output.contains("winapi = "));
+    assert!(output.contains("resources = []"), "{}", output);
+    assert_eq!(*removed.borrow(), ["embed-resource"]);
+    assert!(report.operations.contains(&pre::Operation::RemoveDep {
+        target: None,
+        kind: "dev-dependencies".to_string(),
+        name: "winapi".to_string(),
+    }));
+    assert!(report
+        .removed_by_category
+        .values()
+        .all(|deps| !deps.contains(&"winapi".to_string())));
+}
@@ -0,0 +1,465 @@
+// 命令行工具的集成测试
+
+mod common;
+
+use common::{pre, stderr, stdout, TempDir, WINDOWS_MANIFEST};
+
+/// --dry-run 的标准输出只有 diff，可以直接交给 patch / git apply；摘要输出到 stderr
+#[test]
+fn dry_run_stdout_is_only_the_diff() {
+    let dir = TempDir::new("dry-run-stdout");
+    let input = dir.write("Cargo.toml", WINDOWS_MANIFEST);
+
+    let output = pre(["--dry-run".as_ref(), input.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let diff = stdout(&output);
+    assert!(
+        diff.starts_with("--- "),
+        "标准输出不是以 diff 开头:\n{}",
+        diff
+    );
+    assert!(diff
+        .lines()
+        .all(|line| ["--- ", "+++ ", "@@ ", " ", "+", "-"]
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+            || line.is_empty()));
+    assert!(stderr(&output).contains("删除了 1 个 target 配置"));
+    // 不写入文件
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), WINDOWS_MANIFEST);
+}
+
+/// --dry-run 时 report-only 的将删除条目和 --verbose 的核对信息同样输出到 stderr
+#[test]
+fn dry_run_report_lines_go_to_stderr() {
+    let dir = TempDir::new("dry-run-report-lines");
+    let input = dir.write(
+        "Cargo.toml",
+        format!(
+            "{}\n[target.'cfg(target_os = \"macos\")'.dependencies]\nlibc = \"0.2\"\n",
+            WINDOWS_MANIFEST
+        ),
+    );
+
+    let output = pre([
+        "--dry-run".as_ref(),
+        "--feature-cleaning".as_ref(),
+        "report-only".as_ref(),
+        "--verbose".as_ref(),
+        input.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("--- "), "{}", stdout(&output));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("features.registry 将删除: dep:winreg"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("仍在其他位置声明，已保留: libc"),
+        "{}",
+        stderr
+    );
+}
+
+/// --json 的每个文件摘要带格式版本，报告中包含按分类的删除结果
+#[cfg(feature = "serde")]
+#[test]
+fn json_summary_has_schema_version() {
+    let dir = TempDir::new("json-schema-version");
+    let input = dir.write(
+        "Cargo.toml",
+        "[package]\nname = \"j\"\nversion = \"0.1.0\"\n\n[target.'cfg(windows)'.dependencies]\nwindows-sys = \"0.59\"\n",
+    );
+    let output_path = dir.path().join("out.toml");
+
+    let output = pre([
+        "--json".as_ref(),
+        input.as_os_str(),
+        output_path.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["schema_version"], 1);
+    assert_eq!(json["status"], "changed");
+    assert_eq!(
+        json["report"]["removed_by_category"]["windows"],
+        serde_json::json!(["windows-sys"])
+    );
+}
+
+/// 缓存命中时跳过处理，不再写输出文件；同一个输入换一个输出文件时重新处理
+#[test]
+fn cache_hit_skips_reprocessing() {
+    let dir = TempDir::new("cache-hit");
+    let input = dir.write("in.toml", WINDOWS_MANIFEST);
+    let cache = dir.path().join("cache.toml");
+    let out1 = dir.path().join("out1.toml");
+    let run = |output: &std::path::Path| {
+        let output = pre([
+            "--cache".as_ref(),
+            cache.as_os_str(),
+            input.as_os_str(),
+            output.as_os_str(),
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        stdout(&output)
+    };
+
+    assert!(run(&out1).contains("✓ 处理完成"));
+    let processed = std::fs::read_to_string(&out1).unwrap();
+    assert_ne!(processed, WINDOWS_MANIFEST);
+
+    // 第二次运行命中缓存：输出文件保持原样，说明没有重新处理和写入
+    std::fs::write(&out1, "# 未被覆盖\n").unwrap();
+    assert!(run(&out1).contains("✓ 缓存命中，跳过"));
+    assert_eq!(std::fs::read_to_string(&out1).unwrap(), "# 未被覆盖\n");
+
+    // 另一个已存在的输出文件不算命中
+    let out2 = dir.write("out2.toml", "# 旧内容\n");
+    assert!(run(&out2).contains("✓ 处理完成"));
+    assert_eq!(std::fs::read_to_string(&out2).unwrap(), processed);
+}
+
+/// 目录模式下指向同一个清单的符号链接（文件和目录）只处理一次，链接本身保持为链接
+#[cfg(unix)]
+#[test]
+fn directory_mode_processes_symlinked_manifest_once() {
+    use std::os::unix::fs::symlink;
+
+    let dir = TempDir::new("symlinked-manifest");
+    let real = dir.write("a/Cargo.toml", WINDOWS_MANIFEST);
+    std::fs::create_dir_all(dir.path().join("b")).unwrap();
+    symlink("../a/Cargo.toml", dir.path().join("b/Cargo.toml")).unwrap();
+    symlink("a", dir.path().join("c")).unwrap();
+
+    let output = pre([dir.path()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert_eq!(stdout.matches("✓ 处理完成").count(), 1, "{}", stdout);
+    assert!(stdout.contains("共 1 个文件"), "{}", stdout);
+    assert!(!std::fs::read_to_string(&real).unwrap().contains("winreg"));
+    assert!(std::fs::symlink_metadata(dir.path().join("b/Cargo.toml"))
+        .unwrap()
+        .file_type()
+        .is_symlink());
+}
+
+/// --version-json 输出可以解析的 JSON，包含版本和功能列表
+#[test]
+fn version_json_parses() {
+    let output = pre(["--version-json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    let capabilities = json["capabilities"].as_array().unwrap();
+    assert!(capabilities.contains(&"dry-run".into()));
+    assert_eq!(
+        capabilities.contains(&"json".into()),
+        cfg!(feature = "serde")
+    );
+}
+
+/// 目录模式下某个文件有语法错误时，其他文件照常处理，最后以非零状态退出；
+/// --skip-invalid 时只给出带行列号的警告，不计为失败
+#[test]
+fn directory_mode_continues_past_broken_file() {
+    let dir = TempDir::new("broken-file");
+    let good = dir.write("good/Cargo.toml", WINDOWS_MANIFEST);
+    let broken_content = "[package]\nname = \"broken\nversion = \"0.1.0\"\n";
+    let broken = dir.write("broken/Cargo.toml", broken_content);
+
+    let output = pre([dir.path()]);
+    assert!(!output.status.success());
+    assert!(!std::fs::read_to_string(&good).unwrap().contains("winreg"));
+    assert_eq!(std::fs::read_to_string(&broken).unwrap(), broken_content);
+    assert!(stdout(&output).contains("失败 1 个"), "{}", stdout(&output));
+
+    std::fs::write(&good, WINDOWS_MANIFEST).unwrap();
+    let output = pre(["--skip-invalid".as_ref(), dir.path().as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!std::fs::read_to_string(&good).unwrap().contains("winreg"));
+    assert!(
+        stderr(&output).contains("broken/Cargo.toml:2:"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+/// 命令行工具读取文件和标准输入时，无效的 UTF-8 报告出错的字节位置，不写入输出
+#[test]
+fn invalid_utf8_input_reports_position() {
+    let dir = TempDir::new("invalid-utf8");
+    let input = dir.write("Cargo.toml", b"[package]\nname = \"x\xff\"\n");
+    let output_path = dir.path().join("out.toml");
+
+    let output = pre([input.as_os_str(), output_path.as_os_str()]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("输入不是有效的 UTF-8（第 20 个字节起）"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!output_path.exists());
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_pre"))
+        .arg("-")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), b"name = \"\xff\"\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("输入不是有效的 UTF-8"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+/// 默认经过临时文件重命名覆盖（文件被替换），--no-atomic 时直接覆盖写入原文件
+#[cfg(unix)]
+#[test]
+fn no_atomic_writes_in_place() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = TempDir::new("no-atomic");
+    let input = dir.write("Cargo.toml", WINDOWS_MANIFEST);
+    let inode = |path: &std::path::Path| std::fs::metadata(path).unwrap().ino();
+
+    let before = inode(&input);
+    let output = pre(["--no-atomic".as_ref(), input.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!std::fs::read_to_string(&input).unwrap().contains("winreg"));
+    assert_eq!(inode(&input), before);
+
+    std::fs::write(&input, WINDOWS_MANIFEST).unwrap();
+    let output = pre([&input]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!std::fs::read_to_string(&input).unwrap().contains("winreg"));
+    assert_ne!(inode(&input), before);
+    let names: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(names.len(), 1);
+}
+
+/// --only-if-present target 时没有 [target] 表的清单直接跳过，不写入
+#[test]
+fn only_if_present_skips_manifest_without_table() {
+    let dir = TempDir::new("only-if-present");
+    let with_target = dir.write("a/Cargo.toml", WINDOWS_MANIFEST);
+    let plain_content =
+        "[package]\nname = \"b\"\nversion = \"0.1.0\"\n\n[dependencies]\nlibc = \"0.2\"\n";
+    let plain = dir.write("b/Cargo.toml", plain_content);
+    let modified = std::fs::metadata(&plain).unwrap().modified().unwrap();
+
+    let output = pre([
+        "--only-if-present".as_ref(),
+        "target".as_ref(),
+        dir.path().as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("✓ 缺少指定的表，跳过"), "{}", stdout);
+    assert!(!std::fs::read_to_string(&with_target)
+        .unwrap()
+        .contains("winreg"));
+    assert_eq!(std::fs::read_to_string(&plain).unwrap(), plain_content);
+    assert_eq!(
+        std::fs::metadata(&plain).unwrap().modified().unwrap(),
+        modified
+    );
+}
+
+/// --check 不写入文件：有待删除内容时列出并以 1 退出，已经清理过的清单以 0 退出
+#[test]
+fn check_exit_codes() {
+    let dir = TempDir::new("check");
+    let dirty = dir.write("dirty/Cargo.toml", WINDOWS_MANIFEST);
+    let output = pre(["--check".as_ref(), dirty.as_os_str()]);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let listing = stdout(&output);
+    assert!(listing.contains("✗ 需要清理"), "{}", listing);
+    assert!(
+        listing.contains("删除 target 配置 cfg(windows)"),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("删除 dependencies: winreg"), "{}", listing);
+    assert!(
+        listing.contains("features.registry 删除: dep:winreg"),
+        "{}",
+        listing
+    );
+    assert_eq!(std::fs::read_to_string(&dirty).unwrap(), WINDOWS_MANIFEST);
+
+    let clean_content = pre::process_toml_string(WINDOWS_MANIFEST).unwrap();
+    let clean = dir.write("clean/Cargo.toml", &clean_content);
+    let modified = std::fs::metadata(&clean).unwrap().modified().unwrap();
+    let output = pre(["--check".as_ref(), clean.as_os_str()]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ 无需修改"));
+    assert_eq!(std::fs::read_to_string(&clean).unwrap(), clean_content);
+    assert_eq!(
+        std::fs::metadata(&clean).unwrap().modified().unwrap(),
+        modified
+    );
+}
+
+/// report-only 模式下 features 原样保留，摘要中的 feature 计数写作“将修改”
+#[test]
+fn report_only_leaves_features_unchanged() {
+    let dir = TempDir::new("report-only");
+    let input = dir.write("Cargo.toml", WINDOWS_MANIFEST);
+    let output_path = dir.path().join("out.toml");
+
+    let output = pre([
+        "--feature-cleaning".as_ref(),
+        "report-only".as_ref(),
+        input.as_os_str(),
+        output_path.as_os_str(),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("删除了 1 个 target 配置、1 个依赖，将修改 1 个 feature"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("features.registry 将删除: dep:winreg"),
+        "{}",
+        stdout
+    );
+
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), WINDOWS_MANIFEST);
+    let features = |content: &str| content[content.find("[features]").unwrap()..].to_string();
+    let processed = std::fs::read_to_string(&output_path).unwrap();
+    assert!(!processed.contains("winreg = "));
+    assert_eq!(features(&processed), features(WINDOWS_MANIFEST));
+}
+
+/// --in-place 处理多个文件时，某个文件出错不影响其他文件，最后报告错误并以非零状态退出
+#[test]
+fn in_place_continues_past_invalid_file() {
+    let dir = TempDir::new("in-place-multiple");
+    let first = dir.write("first.toml", WINDOWS_MANIFEST);
+    let broken_content = "[package\nname = \"broken\"\n";
+    let broken = dir.write("broken.toml", broken_content);
+    let second = dir.write("second.toml", WINDOWS_MANIFEST);
+
+    let output = pre([
+        "--in-place".as_ref(),
+        first.as_os_str(),
+        broken.as_os_str(),
+        second.as_os_str(),
+    ]);
+    assert!(!output.status.success());
+    let expected = pre::process_toml_string(WINDOWS_MANIFEST).unwrap();
+    assert_eq!(std::fs::read_to_string(&first).unwrap(), expected);
+    assert_eq!(std::fs::read_to_string(&second).unwrap(), expected);
+    assert_eq!(std::fs::read_to_string(&broken).unwrap(), broken_content);
+    assert!(
+        stderr(&output).contains("broken.toml"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stdout(&output).contains("失败 1 个"), "{}", stdout(&output));
+}
+
+/// --header 再次处理同一个文件时替换已有的生成标记，不重复叠加
+#[test]
+fn header_is_not_duplicated_on_rerun() {
+    let dir = TempDir::new("header-rerun");
+    let input = dir.write("Cargo.toml", WINDOWS_MANIFEST);
+
+    for args in [
+        vec!["--header"],
+        vec!["--header"],
+        vec!["--header", "--keep-arch", "x86_64"],
+    ] {
+        let mut args: Vec<&std::ffi::OsStr> = args.iter().map(|arg| arg.as_ref()).collect();
+        args.push(input.as_os_str());
+        let output = pre(args);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let content = std::fs::read_to_string(&input).unwrap();
+        assert_eq!(
+            content.matches(pre::GENERATED_HEADER_PREFIX).count(),
+            1,
+            "{}",
+            content
+        );
+        assert!(content.starts_with(pre::GENERATED_HEADER_PREFIX));
+    }
+    let content = std::fs::read_to_string(&input).unwrap();
+    assert!(
+        content.lines().next().unwrap().contains("arch=x86_64"),
+        "{}",
+        content
+    );
+    assert_eq!(
+        content.split_once('\n').unwrap().1,
+        pre::process_toml_string(WINDOWS_MANIFEST).unwrap()
+    );
+}
+
+/// --require-content 时只面向 Windows 的清单处理后没有剩余依赖，报错且不写入；不指定时照常写入
+#[test]
+fn require_content_rejects_windows_only_manifest() {
+    let dir = TempDir::new("require-content");
+    let input = dir.write(
+        "Cargo.toml",
+        "[package]\nname = \"windows-only\"\nversion = \"0.1.0\"\n\n[target.'cfg(windows)'.dependencies]\nwinapi = \"0.3\"\nwinreg = \"0.52\"\n",
+    );
+    let output_path = dir.path().join("out.toml");
+
+    let output = pre([
+        "--require-content".as_ref(),
+        input.as_os_str(),
+        output_path.as_os_str(),
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("✗ 错误: windows-only 处理后没有剩余的依赖或 target 配置"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!output_path.exists());
+
+    let output = pre([input.as_os_str(), output_path.as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!std::fs::read_to_string(&output_path)
+        .unwrap()
+        .contains("winapi"));
+}
+
+/// --report-only-changed 时目录模式只列出有改动的文件，无需修改的文件只计入最后的汇总
+#[test]
+fn report_only_changed_omits_unchanged_files() {
+    let dir = TempDir::new("report-only-changed");
+    let changed = dir.write("changed/Cargo.toml", WINDOWS_MANIFEST);
+    let clean = "[package]\nname = \"clean\"\nversion = \"0.1.0\"\n";
+    let clean_a = dir.write("clean-a/Cargo.toml", clean);
+    let clean_b = dir.write("clean-b/Cargo.toml", clean);
+
+    let output = pre(["--report-only-changed".as_ref(), dir.path().as_os_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains(&format!("✓ 处理完成: {}", changed.display())),
+        "{}",
+        stdout
+    );
+    for path in [&clean_a, &clean_b] {
+        assert!(!stdout.contains(&*path.to_string_lossy()), "{}", stdout);
+    }
+    // 只有有改动的文件的摘要
+    assert_eq!(stdout.matches("删除了").count(), 1, "{}", stdout);
+    assert!(
+        stdout.contains("共 3 个文件，失败 0 个，2 个无需修改"),
+        "{}",
+        stdout
+    );
+}
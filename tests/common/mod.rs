@@ -0,0 +1,72 @@
+// 集成测试共用的辅助函数，每个测试文件只用到其中一部分
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// 测试用的临时目录，离开作用域时删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` 在同一个测试文件中应唯一，避免并行运行的测试互相影响
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("pre-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// 在目录中写入文件（自动创建上级目录），返回文件路径
+    pub fn write(&self, relative: &str, content: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.0.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 运行命令行工具
+pub fn pre<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    Command::new(env!("CARGO_BIN_EXE_pre"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+/// 带一个 Windows 平台依赖（以及引用它的 feature）的清单，处理后会有改动
+pub const WINDOWS_MANIFEST: &str = r#"[package]
+name = "sample"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+libc = "0.2"
+
+[target.'cfg(windows)'.dependencies]
+winreg = { version = "0.52", optional = true }
+
+[features]
+registry = ["dep:winreg"]
+"#;
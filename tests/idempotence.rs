@@ -0,0 +1,70 @@
+// 处理结果的幂等性：test 目录下的每个用例处理一次的输出再处理一次，结果必须完全相同
+
+mod common;
+
+use common::{stderr, TempDir};
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// 用例开头注释中 "由 `pre <选项>` 生成" 的选项，两次处理都使用
+fn fixture_options(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .find_map(|line| {
+            let rest = &line[line.find("由 `pre ")? + "由 `pre ".len()..];
+            Some(rest[..rest.find("` 生成")?].to_string())
+        })
+        .map(|options| options.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// 在仓库根目录运行，用例选项中的相对路径（例如 `--known-deps test/...`）才能找到
+fn run(options: &[String], input: &Path, output: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pre"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(options)
+        .arg(input)
+        .arg(output)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn processing_fixtures_twice_changes_nothing() {
+    let dir = TempDir::new("idempotence");
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+    let mut checked = 0;
+    let mut entries: Vec<_> = std::fs::read_dir(&fixtures)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    entries.sort();
+
+    for input in entries {
+        let name = input.file_name().unwrap().to_str().unwrap();
+        let options = fixture_options(&std::fs::read_to_string(&input).unwrap());
+        let once = dir.path().join(format!("once-{}", name));
+        let twice = dir.path().join(format!("twice-{}", name));
+
+        // 第一次处理出错的用例（例如预期报错的用例）跳过
+        if !run(&options, &input, &once).status.success() {
+            continue;
+        }
+        let output = run(&options, &once, &twice);
+        assert!(
+            output.status.success(),
+            "{} 的输出再次处理时出错: {}",
+            name,
+            stderr(&output)
+        );
+        assert_eq!(
+            std::fs::read_to_string(&once).unwrap(),
+            std::fs::read_to_string(&twice).unwrap(),
+            "{} 再次处理后结果发生变化",
+            name
+        );
+        checked += 1;
+    }
+    assert!(checked > 50, "只检查了 {} 个用例", checked);
+}
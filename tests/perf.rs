@@ -0,0 +1,34 @@
+// 性能回退检查：处理约 1 万行的合成清单不能超过时间上限
+//
+// 上限留有足够余量，debug 构建也不会误报；在特别慢的环境（例如 valgrind 下）可以设置
+// PRE_SKIP_PERF_TEST=1 跳过
+
+// 与 benches/process.rs 共用的合成清单生成器
+#[path = "../benches/synthetic/mod.rs"]
+mod synthetic;
+
+use std::time::Instant;
+use synthetic::{synthetic_manifest, CHECK_TARGETS, CHECK_THRESHOLD};
+
+#[test]
+fn ten_thousand_line_manifest_within_threshold() {
+    if std::env::var_os("PRE_SKIP_PERF_TEST").is_some() {
+        eprintln!("已设置 PRE_SKIP_PERF_TEST，跳过性能检查");
+        return;
+    }
+    let content = synthetic_manifest(CHECK_TARGETS);
+    let lines = content.lines().count();
+    assert!(lines >= 9_000, "合成清单只有 {} 行", lines);
+
+    let start = Instant::now();
+    let output = pre::process_toml_string(&content).unwrap();
+    let elapsed = start.elapsed();
+    assert!(output.len() < content.len());
+    assert!(
+        elapsed <= CHECK_THRESHOLD,
+        "处理 {} 行的清单耗时 {:?}，超过上限 {:?}",
+        lines,
+        elapsed,
+        CHECK_THRESHOLD
+    );
+}